@@ -0,0 +1,200 @@
+//! Player authentication against a session server, and offline-mode identities
+
+use md5::Md5;
+use protocol::{packets::login::Property, BString};
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use uuid::Uuid;
+
+pub const DEFAULT_SESSION_SERVER: &str = "https://sessionserver.mojang.com";
+
+/// An authenticated player profile
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameProfile {
+    pub uuid: Uuid,
+    pub name: String,
+    /// Usually contains the `textures` property with the player's skin and cape
+    pub properties: Vec<Property>,
+}
+
+#[derive(Deserialize)]
+struct ProfileJson {
+    id: Uuid,
+    name: String,
+    #[serde(default)]
+    properties: Vec<PropertyJson>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct PropertyJson {
+    name: String,
+    value: String,
+    signature: Option<String>,
+}
+
+/// Returns `None` if any of the properties are too long
+pub(crate) fn properties_from_json(properties: Vec<PropertyJson>) -> Option<Vec<Property>> {
+    properties
+        .into_iter()
+        .map(|property| {
+            Some(Property {
+                name: BString::new(property.name)?,
+                value: BString::new(property.value)?,
+                signature: match property.signature {
+                    Some(signature) => Some(BString::new(signature)?),
+                    None => None,
+                },
+            })
+        })
+        .collect()
+}
+
+/// Computes the server hash used in authentication.
+///
+/// This is Minecraft's non-standard hex digest: the SHA1 hash is interpreted as a
+/// signed two's complement number and printed in hex without leading zeros.
+pub fn server_hash(server_id: &str, shared_secret: &[u8], public_key: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(server_id.as_bytes());
+    hasher.update(shared_secret);
+    hasher.update(public_key);
+    let mut hash = hasher.finalize();
+
+    let negative = hash[0] & 0b1000_0000 != 0;
+    if negative {
+        // Perform two's complement
+        let mut carry = true;
+        for byte in hash.iter_mut().rev() {
+            *byte = !*byte;
+            if carry {
+                carry = *byte == 0xFF;
+                *byte = byte.wrapping_add(1);
+            }
+        }
+    }
+
+    let digits = format!("{hash:x}");
+    let digits = digits.trim_start_matches('0');
+
+    format!("{}{digits}", if negative { "-" } else { "" })
+}
+
+/// Returns the UUID the vanilla server uses for players in offline mode.
+///
+/// It's a version 3 UUID of `OfflinePlayer:<name>`, without a namespace.
+pub fn offline_uuid(username: &str) -> Uuid {
+    let hash = Md5::digest(format!("OfflinePlayer:{username}").as_bytes());
+
+    uuid::Builder::from_md5_bytes(hash.into()).into_uuid()
+}
+
+/// Checks whether the player has joined the server according to the session server.
+///
+/// `session_server` is the base URL, for example [`DEFAULT_SESSION_SERVER`].
+///
+/// Returns `None` if the player is not authenticated.
+pub async fn has_joined(
+    client: &reqwest::Client,
+    session_server: &str,
+    username: &str,
+    server_hash: &str,
+) -> Result<Option<GameProfile>, Box<dyn std::error::Error + Send + Sync>> {
+    let url = format!(
+        "{}/session/minecraft/hasJoined",
+        session_server.trim_end_matches('/')
+    );
+
+    let response = client
+        .get(url)
+        .query(&[("username", username), ("serverId", server_hash)])
+        .send()
+        .await?
+        .error_for_status()?;
+
+    if response.status() == reqwest::StatusCode::NO_CONTENT {
+        return Ok(None);
+    }
+
+    let profile: ProfileJson = serde_json::from_str(&response.text().await?)?;
+
+    let properties = properties_from_json(profile.properties).ok_or("Profile property too long")?;
+
+    Ok(Some(GameProfile {
+        uuid: profile.id,
+        name: profile.name,
+        properties,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{has_joined, offline_uuid, server_hash};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+    use uuid::Uuid;
+
+    #[test]
+    fn server_hash_digest() {
+        // Known values from wiki.vg
+        let samples = [
+            ("Notch", "4ed1f46bbe04bc756bcb17c0c7ce3e4632f06a48"),
+            ("jeb_", "-7c9d5b0044c130109a5d7b5fb5c317c02b4e28c1"),
+            ("simon", "88e16a1019277b15d58faf0541e11910eb756f6"),
+        ];
+
+        for (input, digest) in samples {
+            assert_eq!(server_hash(input, &[], &[]), digest);
+        }
+    }
+
+    #[test]
+    fn offline_uuids() {
+        assert_eq!(
+            offline_uuid("Notch"),
+            Uuid::parse_str("b50ad385-829d-3141-a216-7e7d7539ba7f").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn has_joined_mock() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let body = r#"{
+                "id": "069a79f444e94726a5befca90e38aaf5",
+                "name": "Notch",
+                "properties": [{ "name": "textures", "value": "e30=", "signature": "c2ln" }]
+            }"#;
+
+            let (mut socket, _) = listener.accept().await.unwrap();
+
+            let mut request = vec![0u8; 1024];
+            let n = socket.read(&mut request).await.unwrap();
+            let request = String::from_utf8_lossy(&request[..n]);
+            assert!(request
+                .starts_with("GET /session/minecraft/hasJoined?username=Notch&serverId=-7c9d5b00"));
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let profile = has_joined(&reqwest::Client::new(), &base_url, "Notch", "-7c9d5b00")
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            profile.uuid,
+            Uuid::parse_str("069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap()
+        );
+        assert_eq!(profile.name, "Notch");
+        assert_eq!(*profile.properties[0].name, "textures");
+        assert_eq!(profile.properties[0].signature.as_deref().unwrap(), "c2ln");
+    }
+}
@@ -1,27 +1,72 @@
-use futures::Future;
+use auth::GameProfile;
+use futures::future::BoxFuture;
 use graceful_exit::GracefulExit;
 use networking::{Conn, ConnCtx};
+use protocol::{
+    codec::FrameCodec,
+    packets::{login::Disconnect, status::StatusResponse, ClientBound, ServerBound},
+    version::ProtocolVersion,
+};
+use rsa::{pkcs8::EncodePublicKey, RsaPrivateKey};
 use slab::Slab;
-use std::{collections::HashMap, sync::Arc};
+use std::{net::SocketAddr, sync::Arc};
 use tokio::{
-    io::BufReader,
     net::TcpListener,
     select,
     sync::{broadcast, mpsc::unbounded_channel, RwLock},
 };
+use tokio_util::codec::Framed;
 use tracing::error;
 
-pub use networking::legacy_ping::{LegacyPingPayload, LegacyPingResponse};
+pub use networking::{
+    configuration::{Configuration, RegistryEntry},
+    encryption::EncryptedStream,
+    forwarding::Forwarding,
+    legacy_ping::{LegacyPingPayload, LegacyPingResponse},
+    login::{LoginConn, LoginFailure, PreLoginPayload},
+    proxy_protocol::ProxyProtocol,
+    status::StatusPayload,
+};
+pub use protocol;
 
+pub mod auth;
 mod networking;
 
 pub struct Server {
     connections: RwLock<Slab<Conn>>,
     graceful_exit: GracefulExit,
+    rsa_keypair: RsaPrivateKey,
+    /// DER-encoded public key of `rsa_keypair`
+    rsa_public_key: Vec<u8>,
+    http_client: reqwest::Client,
+    pub options: ServerOptions,
     pub global_events: GlobalEvents,
 }
 
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    /// Packets at least this long (in bytes) will be compressed.
+    /// `None` disables compression.
+    pub compression_threshold: Option<usize>,
+    /// Whether to encrypt connections and authenticate players with the session server.
+    ///
+    /// In offline mode anyone can join with any username, and players get
+    /// [offline UUIDs][auth::offline_uuid].
+    pub online_mode: bool,
+    /// Base URL of the session server used to authenticate players
+    pub session_server: String,
+    /// Player information forwarding from a proxy.
+    ///
+    /// If enabled, connections without valid forwarded information are rejected,
+    /// and players are not authenticated by this server, regardless of `online_mode`.
+    pub forwarding: Forwarding,
+    /// Whether to expect HAProxy PROXY protocol headers on new connections,
+    /// for when the server is behind a TCP load balancer
+    pub proxy_protocol: ProxyProtocol,
+}
+
 #[derive(Default)]
+#[allow(clippy::type_complexity)]
 pub struct GlobalEvents {
     pub legacy_ping: Vec<
         fn(
@@ -31,16 +76,114 @@ pub struct GlobalEvents {
             response: &mut Option<LegacyPingResponse>,
         ),
     >,
+    /// Server list ping (1.7+)
+    ///
+    /// Leaving the response as `None` makes the server appear offline.
+    /// Use [`StatusResponseBuilder`][protocol::packets::status::StatusResponseBuilder] to build a response.
+    pub status: Vec<
+        fn(
+            server: Arc<Server>,
+            id: usize,
+            payload: &StatusPayload,
+            response: &mut Option<StatusResponse>,
+        ),
+    >,
+    /// Right after the client starts logging in, before authentication
+    ///
+    /// Setting `rejection` disconnects the client.
+    pub pre_login: Vec<
+        fn(
+            server: Arc<Server>,
+            id: usize,
+            payload: &PreLoginPayload,
+            rejection: &mut Option<Disconnect>,
+        ),
+    >,
+    /// After the player is authenticated (or given an offline profile), for login-time
+    /// negotiations using [login plugin messages][LoginConn::plugin_request]
+    ///
+    /// Handlers are run one after another. Returning a rejection disconnects the client.
+    pub login_plugin: Vec<
+        for<'a> fn(
+            server: Arc<Server>,
+            conn: &'a mut LoginConn<'_>,
+        ) -> BoxFuture<'a, Option<Disconnect>>,
+    >,
+    /// After the player is authenticated (or given an offline profile), before the login succeeds
+    ///
+    /// The profile can be modified. Setting `rejection` disconnects the client.
+    pub authenticated: Vec<
+        fn(
+            server: Arc<Server>,
+            id: usize,
+            profile: &mut GameProfile,
+            rejection: &mut Option<Disconnect>,
+        ),
+    >,
+    /// In the configuration state (1.20.2+), after the login and before the player enters play
+    ///
+    /// Handlers add the registries, tags and resource packs sent to the client.
+    pub configuration: Vec<fn(server: Arc<Server>, id: usize, configuration: &mut Configuration)>,
+    /// When a login fails for any reason, right before the client is disconnected
+    pub login_failed:
+        Vec<fn(server: Arc<Server>, id: usize, username: &str, reason: &LoginFailure)>,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            compression_threshold: Some(256),
+            online_mode: true,
+            session_server: auth::DEFAULT_SESSION_SERVER.to_string(),
+            forwarding: Forwarding::None,
+            proxy_protocol: ProxyProtocol::Disabled,
+        }
+    }
 }
 
 impl Server {
     pub fn new() -> Self {
+        let rsa_keypair = RsaPrivateKey::new(&mut rand::thread_rng(), 1024)
+            .expect("failed to generate RSA keypair");
+        let rsa_public_key = rsa_keypair
+            .to_public_key()
+            .to_public_key_der()
+            .expect("failed to encode RSA public key")
+            .into_vec();
+
         Self {
             connections: RwLock::new(Slab::new()),
             graceful_exit: GracefulExit::new(),
+            rsa_keypair,
+            rsa_public_key,
+            http_client: reqwest::Client::new(),
+            options: Default::default(),
             global_events: Default::default(),
         }
     }
+    /// Returns the address of the connection, or `None` if no such connection exists
+    pub async fn conn_addr(&self, id: usize) -> Option<SocketAddr> {
+        self.connections.read().await.get(id).map(|conn| conn.addr)
+    }
+    /// Subscribes to all packets received from the connection
+    ///
+    /// Returns `None` if no such connection exists
+    pub async fn subscribe(&self, id: usize) -> Option<broadcast::Receiver<ServerBound>> {
+        self.connections
+            .read()
+            .await
+            .get(id)
+            .map(|conn| conn.input.resubscribe())
+    }
+    /// Queues a packet to be sent to the connection once it reaches the play state
+    ///
+    /// Returns `false` if no such connection exists
+    pub async fn send_packet(&self, id: usize, packet: impl Into<ClientBound>) -> bool {
+        match self.connections.read().await.get(id) {
+            Some(conn) => conn.output.send(packet.into()).is_ok(),
+            None => false,
+        }
+    }
     pub async fn run(self, tcp_listener: TcpListener) -> Result<(), Box<dyn std::error::Error>> {
         let server = Arc::new(self);
 
@@ -60,7 +203,7 @@ impl Server {
             let (output_writer, output_reader) = unbounded_channel();
 
             let id = server.connections.write().await.insert(Conn {
-                addr: addr.clone(),
+                addr,
                 input: input_reader,
                 output: output_writer,
             });
@@ -69,21 +212,31 @@ impl Server {
             tokio::spawn(async move {
                 // handle connection
                 if let Err(e) = networking::handle_new_conn(
-                    server,
+                    server.clone(),
                     ConnCtx {
                         id,
-                        stream: BufReader::new(socket),
+                        stream: Framed::new(EncryptedStream::new(socket), FrameCodec::new()),
                         addr,
                         input: input_writer,
                         output: output_reader,
                         buf: Vec::new(),
+                        // The handshake is the same in all versions
+                        version: ProtocolVersion::LATEST,
                     },
                 )
                 .await
                 {
-                    error!("Stream error: {e:?}");
+                    error!("Stream error: {e}");
                 }
+
+                server.connections.write().await.remove(id);
             });
         }
     }
 }
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
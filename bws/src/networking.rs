@@ -1,16 +1,37 @@
+pub(crate) mod configuration;
+pub(crate) mod encryption;
+pub(crate) mod forwarding;
 pub(crate) mod legacy_ping;
+pub(crate) mod login;
+mod play;
+pub(crate) mod proxy_protocol;
+pub(crate) mod status;
 
 use crate::Server;
-use protocol::packets::{ClientBound, ServerBound};
-use std::{net::SocketAddr, sync::Arc};
+use encryption::EncryptedStream;
+use forwarding::Forwarding;
+use futures::{SinkExt, StreamExt};
+use protocol::{
+    codec::FrameCodec,
+    limits::{with_limits, DecodeLimits},
+    newtypes::NextState,
+    packets::{ClientBound, SBHandshake, ServerBound},
+    version::ForVersion,
+    DecodeError, FromBytesVersioned, ProtocolVersion, ToBytesVersioned,
+};
+use proxy_protocol::{Header, ProxyProtocol};
+use std::{io, net::SocketAddr, sync::Arc};
 use tokio::{
-    io::{AsyncBufReadExt, BufReader},
     net::TcpStream,
+    select,
     sync::{
         broadcast::{Receiver, Sender},
         mpsc::{UnboundedReceiver, UnboundedSender},
     },
+    time::timeout,
 };
+use tokio_util::codec::Framed;
+use tracing::debug;
 
 pub struct Conn {
     pub(crate) addr: SocketAddr,
@@ -20,19 +41,315 @@ pub struct Conn {
 
 pub(crate) struct ConnCtx {
     pub id: usize,
-    pub stream: BufReader<TcpStream>,
+    /// Handles framing and compression, with encryption done by the [`EncryptedStream`]
+    /// under it.
+    ///
+    /// Use `get_mut` to read and write the stream directly before the handshake.
+    pub stream: Framed<EncryptedStream<TcpStream>, FrameCodec>,
     pub addr: SocketAddr,
     pub input: Sender<ServerBound>,
     pub output: UnboundedReceiver<ClientBound>,
     pub buf: Vec<u8>,
+    /// Protocol version from the handshake, which determines the packet mapping
+    pub version: ProtocolVersion,
+}
+
+impl ConnCtx {
+    /// Reads a single packet from the stream and publishes it to the connection's input
+    pub async fn read_packet<P: FromBytesVersioned + Clone + Into<ServerBound>>(
+        &mut self,
+    ) -> io::Result<P> {
+        let packet: P = self.read_unpublished().await?;
+
+        // Errors only if there are no subscribers, which is fine
+        let _ = self.input.send(packet.clone().into());
+
+        Ok(packet)
+    }
+    /// Reads a single packet from the stream, for packets that aren't [`ServerBound`]
+    pub async fn read_unpublished<P: FromBytesVersioned>(&mut self) -> io::Result<P> {
+        let frame = self
+            .stream
+            .next()
+            .await
+            .ok_or(io::ErrorKind::UnexpectedEof)??;
+        let mut slice = &frame[..];
+        let packet = with_limits(DecodeLimits::for_frame(frame.len()), || {
+            P::read_versioned(&mut slice, self.version)
+        })
+        .map_err(|e| DecodeError::at_offset(e, frame.len() - slice.len()))?;
+
+        Ok(packet)
+    }
+    /// Enables encryption of the stream, including data already read but not yet decoded
+    pub fn enable_encryption(&mut self, shared_secret: &[u8; 16]) {
+        let mut read_ahead = std::mem::take(self.stream.read_buffer_mut());
+        self.stream
+            .get_mut()
+            .enable_encryption(shared_secret, &mut read_ahead);
+        *self.stream.read_buffer_mut() = read_ahead;
+    }
+    /// Changes the address of the connection, for example when it's forwarded by a proxy
+    pub async fn set_addr(&mut self, server: &Server, addr: SocketAddr) {
+        self.addr = addr;
+
+        if let Some(conn) = server.connections.write().await.get_mut(self.id) {
+            conn.addr = addr;
+        }
+    }
+    /// Writes a single packet to the stream
+    pub async fn write_packet<P: ToBytesVersioned + Into<ClientBound>>(
+        &mut self,
+        packet: &P,
+    ) -> io::Result<()> {
+        self.stream.send(ForVersion(packet, self.version)).await
+    }
 }
 
 pub(crate) async fn handle_new_conn(
     server: Arc<Server>,
     mut ctx: ConnCtx,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = server.graceful_exit.guard();
+
+    if server.options.proxy_protocol != ProxyProtocol::Disabled {
+        let header = timeout(
+            proxy_protocol::HEADER_TIMEOUT,
+            proxy_protocol::read_header(ctx.stream.get_mut(), server.options.proxy_protocol),
+        );
+        let header = select! {
+            header = header => header.map_err(|_| {
+                io::Error::new(io::ErrorKind::TimedOut, "PROXY header timed out")
+            })??,
+            _ = server.graceful_exit.wait_for_exit() => return Ok(()),
+        };
+
+        match header {
+            Header::Proxy(Some(addr)) => ctx.set_addr(&server, addr).await,
+            Header::Proxy(None) => {}
+            // Decoded with the rest of the first packet
+            Header::Missing(read) => ctx.stream.read_buffer_mut().extend_from_slice(&read),
+        }
+    }
+
     // Handle legacy ping
-    legacy_ping::handle(server, &mut ctx).await?;
+    if legacy_ping::handle(server.clone(), &mut ctx).await? {
+        return Ok(());
+    }
+
+    // BungeeCord forwarding needs the full server address, which vanilla limits
+    let read_handshake = async {
+        if server.options.forwarding == Forwarding::BungeeCord {
+            let (handshake, server_address) =
+                forwarding::read_bungeecord_handshake(&mut ctx).await?;
+            Ok::<_, io::Error>((handshake, Some(server_address)))
+        } else {
+            match ctx.read_packet().await? {
+                SBHandshake::Handshake(handshake) => Ok((handshake, None)),
+            }
+        }
+    };
+    let (handshake, bungeecord_address) = select! {
+        r = read_handshake => r?,
+        _ = server.graceful_exit.wait_for_exit() => return Ok(()),
+    };
+
+    ctx.version = handshake.protocol_version.into();
+
+    debug!(
+        "{} handshake: protocol {}, next state {:?}",
+        ctx.addr, handshake.protocol_version.0, handshake.next_state
+    );
+
+    match handshake.next_state {
+        NextState::Status => select! {
+            r = status::handle(&server, &mut ctx, &handshake) => r?,
+            _ = server.graceful_exit.wait_for_exit() => {},
+        },
+        NextState::Login => {
+            let logged_in = select! {
+                r = login::handle(&server, &mut ctx, &handshake, bungeecord_address.as_deref()) => Some(r?),
+                _ = server.graceful_exit.wait_for_exit() => None,
+            };
+
+            let Some(logged_in) = logged_in else {
+                login::disconnect(&mut ctx, "Server shutting down").await?;
+                return Ok(());
+            };
+
+            if !logged_in {
+                return Ok(());
+            }
+
+            // The configuration state was added in 1.20.2
+            if ctx.version >= ProtocolVersion::V1_20_2 {
+                let configured = select! {
+                    r = configuration::handle(&server, &mut ctx) => r?,
+                    _ = server.graceful_exit.wait_for_exit() => false,
+                };
+
+                if !configured {
+                    return Ok(());
+                }
+            }
+
+            play::handle(&server, &mut ctx).await?;
+        }
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{handle_new_conn, ConnCtx, EncryptedStream};
+    use crate::Server;
+    use futures::{SinkExt, StreamExt};
+    use protocol::{
+        codec::FrameCodec,
+        nbt::{Compound, Nbt},
+        newtypes::{BString, NextState},
+        packets::{
+            handshake::Handshake, login::LoginStart, play::KeepAlive, CBConfiguration, CBLogin,
+            CBPlay, ClientBound, SBConfiguration, SBHandshake, SBLogin, SBPlay, ServerBound,
+        },
+        version::ForVersion,
+        FromBytesVersioned, ProtocolVersion, ToBytesVersioned, VarInt,
+    };
+    use std::sync::Arc;
+    use tokio::{
+        net::{TcpListener, TcpStream},
+        sync::{broadcast, mpsc::unbounded_channel},
+    };
+    use tokio_util::codec::Framed;
+
+    const VERSION: ProtocolVersion = ProtocolVersion::V1_20_2;
+
+    async fn send<P: ToBytesVersioned>(client: &mut Framed<TcpStream, FrameCodec>, packet: P) {
+        client.send(ForVersion(&packet, VERSION)).await.unwrap();
+    }
+
+    async fn receive<P: FromBytesVersioned>(client: &mut Framed<TcpStream, FrameCodec>) -> P {
+        let frame = client.next().await.unwrap().unwrap();
+        P::read_versioned(&mut &frame[..], VERSION).unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn login_configuration_play() {
+        let mut server = Server::new();
+        server.options.online_mode = false;
+        server.options.compression_threshold = Some(64);
+        server
+            .global_events
+            .configuration
+            .push(|_, _, configuration| {
+                configuration.add_registry_entry(
+                    "minecraft:dimension_type",
+                    "minecraft:overworld",
+                    Nbt::Compound(Compound::new()),
+                );
+            });
+        let server = Arc::new(server);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = Framed::new(
+            TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap(),
+            FrameCodec::new(),
+        );
+        let (socket, addr) = listener.accept().await.unwrap();
+
+        let (input_writer, mut input_reader) = broadcast::channel(16);
+        let (output_writer, output_reader) = unbounded_channel();
+        let conn = tokio::spawn(async move {
+            handle_new_conn(
+                server,
+                ConnCtx {
+                    id: 0,
+                    stream: Framed::new(EncryptedStream::new(socket), FrameCodec::new()),
+                    addr,
+                    input: input_writer,
+                    output: output_reader,
+                    buf: Vec::new(),
+                    version: ProtocolVersion::LATEST,
+                },
+            )
+            .await
+            // The error isn't `Send`
+            .map_err(|e| e.to_string())
+        });
+
+        send(
+            &mut client,
+            SBHandshake::Handshake(Handshake {
+                protocol_version: VarInt(VERSION.0),
+                server_address: BString::new("localhost".to_string()).unwrap(),
+                server_port: 25565,
+                next_state: NextState::Login,
+            }),
+        )
+        .await;
+        send(
+            &mut client,
+            SBLogin::LoginStart(LoginStart {
+                name: BString::new("Steve".to_string()).unwrap(),
+                uuid: Some(crate::auth::offline_uuid("Steve")),
+            }),
+        )
+        .await;
+
+        loop {
+            match receive(&mut client).await {
+                CBLogin::SetCompression(compression) => {
+                    client
+                        .codec_mut()
+                        .enable_compression(compression.threshold.0 as usize);
+                }
+                CBLogin::LoginSuccess(success) => {
+                    assert_eq!(*success.username, "Steve");
+                    break;
+                }
+                other => panic!("unexpected login packet {other:?}"),
+            }
+        }
+        send(&mut client, SBLogin::LoginAcknowledged).await;
+
+        assert!(matches!(
+            receive(&mut client).await,
+            CBConfiguration::FeatureFlags(_)
+        ));
+        let CBConfiguration::RegistryData(registries) = receive(&mut client).await else {
+            panic!("expected the registries");
+        };
+        let codec = registries.registry_codec.0.unwrap();
+        assert!(codec.get("minecraft:dimension_type").is_some());
+        assert_eq!(
+            receive::<CBConfiguration>(&mut client).await,
+            CBConfiguration::FinishConfiguration
+        );
+        send(&mut client, SBConfiguration::FinishConfiguration).await;
+
+        // Play packets are mapped for the client's version both ways
+        output_writer
+            .send(ClientBound::Play(CBPlay::KeepAlive(KeepAlive { id: 7 })))
+            .unwrap();
+        let frame = client.next().await.unwrap().unwrap();
+        assert_eq!(frame[0], 0x24);
+        assert_eq!(
+            CBPlay::read_versioned(&mut &frame[..], VERSION).unwrap(),
+            CBPlay::KeepAlive(KeepAlive { id: 7 })
+        );
+
+        send(&mut client, SBPlay::KeepAlive(KeepAlive { id: 7 })).await;
+        loop {
+            if let ServerBound::Play(packet) = input_reader.recv().await.unwrap() {
+                assert_eq!(packet, SBPlay::KeepAlive(KeepAlive { id: 7 }));
+                break;
+            }
+        }
+
+        drop(client);
+        conn.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+use super::ConnCtx;
+use crate::Server;
+use protocol::{
+    nbt::{Compound, Nbt, NetworkNbt},
+    packets::{
+        configuration::{FeatureFlags, RegistryData, RegistryTags, ResourcePack, UpdateTags},
+        login::Disconnect,
+        CBConfiguration, SBConfiguration,
+    },
+    TextComponent,
+};
+use std::{collections::BTreeMap, io, sync::Arc};
+use tracing::debug;
+
+/// What is sent to the client in the configuration state, before it enters play
+#[derive(Debug, Clone)]
+pub struct Configuration {
+    /// Entries of each registry, such as `minecraft:dimension_type`. An entry's id is
+    /// its index.
+    pub registries: BTreeMap<String, Vec<RegistryEntry>>,
+    /// `minecraft:vanilla` by default
+    pub feature_flags: Vec<String>,
+    pub tags: Vec<RegistryTags>,
+    /// Sent one after another, waiting for the client to load each. The client is
+    /// disconnected if it doesn't load a forced one.
+    pub resource_packs: Vec<ResourcePack>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegistryEntry {
+    pub name: String,
+    pub element: Nbt,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            registries: BTreeMap::new(),
+            feature_flags: vec!["minecraft:vanilla".to_string()],
+            tags: Vec::new(),
+            resource_packs: Vec::new(),
+        }
+    }
+}
+
+impl Configuration {
+    /// Adds an entry to the end of a registry
+    pub fn add_registry_entry(
+        &mut self,
+        registry: impl Into<String>,
+        name: impl Into<String>,
+        element: Nbt,
+    ) {
+        self.registries
+            .entry(registry.into())
+            .or_default()
+            .push(RegistryEntry {
+                name: name.into(),
+                element,
+            });
+    }
+    /// The registries in the format of the registry data packet
+    fn registry_codec(&self) -> Nbt {
+        let mut codec = Compound::new();
+
+        for (registry, entries) in &self.registries {
+            let entries = entries
+                .iter()
+                .enumerate()
+                .map(|(id, entry)| {
+                    let mut compound = Compound::new();
+                    compound.insert("name".to_string(), Nbt::String(entry.name.clone()));
+                    compound.insert("id".to_string(), Nbt::Int(id as i32));
+                    compound.insert("element".to_string(), entry.element.clone());
+
+                    Nbt::Compound(compound)
+                })
+                .collect();
+
+            let mut compound = Compound::new();
+            compound.insert("type".to_string(), Nbt::String(registry.clone()));
+            compound.insert("value".to_string(), Nbt::List(entries));
+
+            codec.insert(registry.clone(), Nbt::Compound(compound));
+        }
+
+        Nbt::Compound(codec)
+    }
+}
+
+/// Returns `true` if the configuration finished and the connection should proceed to the play state
+pub(crate) async fn handle(
+    server: &Arc<Server>,
+    ctx: &mut ConnCtx,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut configuration = Configuration::default();
+
+    for handler in &server.global_events.configuration {
+        handler(server.clone(), ctx.id, &mut configuration);
+    }
+
+    ctx.write_packet(&CBConfiguration::FeatureFlags(FeatureFlags {
+        feature_flags: configuration.feature_flags.clone(),
+    }))
+    .await?;
+
+    ctx.write_packet(&CBConfiguration::RegistryData(RegistryData {
+        registry_codec: NetworkNbt(Some(configuration.registry_codec())),
+    }))
+    .await?;
+
+    if !configuration.tags.is_empty() {
+        ctx.write_packet(&CBConfiguration::UpdateTags(UpdateTags {
+            registries: configuration.tags,
+        }))
+        .await?;
+    }
+
+    for pack in configuration.resource_packs {
+        let forced = pack.forced;
+        ctx.write_packet(&CBConfiguration::ResourcePack(pack))
+            .await?;
+
+        if !resource_pack_loaded(ctx).await? && forced {
+            debug!("{}: forced resource pack not loaded", ctx.addr);
+
+            ctx.write_packet(&CBConfiguration::Disconnect(Disconnect {
+                reason: TextComponent::translate(
+                    "multiplayer.requiredTexturePrompt.disconnect",
+                    Vec::new(),
+                ),
+            }))
+            .await?;
+
+            return Ok(false);
+        }
+    }
+
+    ctx.write_packet(&CBConfiguration::FinishConfiguration)
+        .await?;
+
+    // Other packets, such as the client information, are only published to the input
+    loop {
+        if let SBConfiguration::FinishConfiguration = ctx.read_packet().await? {
+            return Ok(true);
+        }
+    }
+}
+
+/// Waits for the client's final response to a resource pack
+async fn resource_pack_loaded(ctx: &mut ConnCtx) -> io::Result<bool> {
+    loop {
+        if let SBConfiguration::ResourcePackResponse(response) = ctx.read_packet().await? {
+            match response.result {
+                // Accepted, and the download started
+                3 => continue,
+                result => return Ok(result == 0),
+            }
+        }
+    }
+}
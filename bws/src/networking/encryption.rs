@@ -0,0 +1,228 @@
+use aes::{
+    cipher::{generic_array::GenericArray, BlockDecryptMut, BlockEncryptMut, KeyIvInit},
+    Aes128,
+};
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite, ReadBuf};
+
+type Encryptor = cfb8::Encryptor<Aes128>;
+type Decryptor = cfb8::Decryptor<Aes128>;
+
+const READ_BUF_SIZE: usize = 8192;
+
+/// A buffered stream wrapper that transparently encrypts and decrypts everything using
+/// AES/CFB8, once encryption is enabled.
+///
+/// Before that all data passes through unchanged, so it can be used for reading things
+/// like PROXY headers and legacy pings. Bytes already buffered when encryption is enabled
+/// are decrypted too.
+pub struct EncryptedStream<S> {
+    inner: S,
+    cipher: Option<(Encryptor, Decryptor)>,
+    /// Data read from the inner stream, decrypted if encryption is enabled
+    read_buf: Box<[u8]>,
+    read_pos: usize,
+    read_end: usize,
+    /// Encrypted data not yet written to the inner stream
+    write_buf: Vec<u8>,
+}
+
+impl<S> EncryptedStream<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            cipher: None,
+            read_buf: vec![0; READ_BUF_SIZE].into_boxed_slice(),
+            read_pos: 0,
+            read_end: 0,
+            write_buf: Vec::new(),
+        }
+    }
+    /// Enables encryption with the given shared secret, which is used as both the key and the IV.
+    ///
+    /// Everything read or written after this is encrypted. `read_ahead` is data already
+    /// read from this stream but not processed yet, such as the read buffer of a codec,
+    /// and is decrypted in place before the data still buffered here.
+    pub fn enable_encryption(&mut self, shared_secret: &[u8; 16], read_ahead: &mut [u8]) {
+        let key = GenericArray::from_slice(shared_secret);
+        let mut decryptor = Decryptor::new(key, key);
+
+        decrypt(&mut decryptor, read_ahead);
+        decrypt(
+            &mut decryptor,
+            &mut self.read_buf[self.read_pos..self.read_end],
+        );
+
+        self.cipher = Some((Encryptor::new(key, key), decryptor));
+    }
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+    /// Any buffered data is lost
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+/// CFB8 works on single byte blocks
+fn decrypt(decryptor: &mut Decryptor, data: &mut [u8]) {
+    for byte in data.chunks_exact_mut(1) {
+        decryptor.decrypt_block_mut(GenericArray::from_mut_slice(byte));
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncBufRead for EncryptedStream<S> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+
+        if this.read_pos == this.read_end {
+            let mut buf = ReadBuf::new(&mut this.read_buf);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut buf))?;
+            let filled = buf.filled().len();
+
+            if let Some((_, decryptor)) = &mut this.cipher {
+                decrypt(decryptor, &mut this.read_buf[..filled]);
+            }
+
+            this.read_pos = 0;
+            this.read_end = filled;
+        }
+
+        Poll::Ready(Ok(&this.read_buf[this.read_pos..this.read_end]))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+
+        this.read_pos = (this.read_pos + amt).min(this.read_end);
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for EncryptedStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let available = ready!(self.as_mut().poll_fill_buf(cx))?;
+        let amt = available.len().min(buf.remaining());
+        buf.put_slice(&available[..amt]);
+        self.consume(amt);
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for EncryptedStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        let Some((encryptor, _)) = &mut this.cipher else {
+            return Pin::new(&mut this.inner).poll_write(cx, buf);
+        };
+
+        // Encrypted bytes can't be taken back, so they must be buffered until they're
+        // fully written. Only accept new data after the old data is written.
+        ready!(poll_write_buf(&mut this.inner, &mut this.write_buf, cx))?;
+
+        let start = this.write_buf.len();
+        this.write_buf.extend_from_slice(buf);
+        for byte in this.write_buf[start..].chunks_exact_mut(1) {
+            encryptor.encrypt_block_mut(GenericArray::from_mut_slice(byte));
+        }
+
+        // Try to write it right away, but the data is already accepted either way
+        if let Poll::Ready(Err(e)) = poll_write_buf(&mut this.inner, &mut this.write_buf, cx) {
+            return Poll::Ready(Err(e));
+        }
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        ready!(poll_write_buf(&mut this.inner, &mut this.write_buf, cx))?;
+
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        ready!(poll_write_buf(&mut this.inner, &mut this.write_buf, cx))?;
+
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+/// Writes as much of the pending encrypted data as possible
+fn poll_write_buf<S: AsyncWrite + Unpin>(
+    inner: &mut S,
+    write_buf: &mut Vec<u8>,
+    cx: &mut Context<'_>,
+) -> Poll<io::Result<()>> {
+    while !write_buf.is_empty() {
+        let written = ready!(Pin::new(&mut *inner).poll_write(cx, write_buf))?;
+
+        if written == 0 {
+            return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+        }
+
+        write_buf.drain(..written);
+    }
+
+    Poll::Ready(Ok(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EncryptedStream;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn encryption_mid_stream() {
+        let key = [3u8; 16];
+
+        let mut writer = EncryptedStream::new(Vec::new());
+        writer.write_all(b"plain").await.unwrap();
+        writer.enable_encryption(&key, &mut []);
+        writer.write_all(b"secret, and more").await.unwrap();
+        writer.flush().await.unwrap();
+
+        let written = writer.into_inner();
+        assert_eq!(&written[..5], b"plain");
+        assert_ne!(&written[5..11], b"secret");
+
+        // The whole stream is buffered before encryption is enabled, and a codec already
+        // took some of the encrypted bytes
+        let mut reader = EncryptedStream::new(&written[..]);
+        assert_eq!(reader.fill_buf().await.unwrap(), &written[..]);
+        let mut buf = [0u8; 5];
+        reader.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"plain");
+
+        let mut read_ahead = [0u8; 6];
+        reader.read_exact(&mut read_ahead).await.unwrap();
+        reader.enable_encryption(&key, &mut read_ahead);
+        assert_eq!(&read_ahead, b"secret");
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, b", and more");
+    }
+}
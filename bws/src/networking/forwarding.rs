@@ -0,0 +1,217 @@
+use super::ConnCtx;
+use crate::auth::{self, GameProfile};
+use hmac::{Hmac, Mac};
+use protocol::{
+    newtypes::NextState,
+    packets::{handshake::Handshake, login::Property, SBHandshake, ServerBound},
+    BString, FromBytes, FromBytesVersioned, VarInt,
+};
+use sha2::Sha256;
+use std::{io, net::IpAddr};
+use uuid::Uuid;
+
+pub(crate) const VELOCITY_CHANNEL: &str = "velocity:player_info";
+/// Velocity modern forwarding version requested from the proxy
+pub(crate) const VELOCITY_VERSION: u8 = 1;
+
+/// Player information forwarding from a proxy
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Forwarding {
+    /// Players connect directly
+    #[default]
+    None,
+    /// BungeeCord legacy IP forwarding, where the player information is smuggled
+    /// in the handshake.
+    ///
+    /// It's not signed in any way, so make sure only the proxy can reach the server.
+    BungeeCord,
+    /// Velocity modern forwarding, signed with a secret shared with the proxy
+    Velocity { secret: Vec<u8> },
+}
+
+/// Player information forwarded by a proxy
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Forwarded {
+    pub ip: IpAddr,
+    pub profile: GameProfile,
+}
+
+/// [`SBHandshake`] with the server address allowed past the vanilla limit of 255
+/// characters, to fit the BungeeCord forwarding data
+#[derive(FromBytes, FromBytesVersioned)]
+enum SBBungeeCordHandshake {
+    Handshake(BungeeCordHandshake),
+}
+
+#[derive(FromBytes)]
+struct BungeeCordHandshake {
+    protocol_version: VarInt,
+    server_address: BString<32767>,
+    server_port: u16,
+    next_state: NextState,
+}
+
+#[derive(FromBytes)]
+struct VelocityPlayerInfo {
+    version: VarInt,
+    address: String,
+    uuid: Uuid,
+    username: BString<16>,
+    properties: Vec<Property>,
+}
+
+/// Reads the handshake of a connection with BungeeCord forwarding and publishes it with
+/// just the host as the server address
+///
+/// Returns the handshake and the full server address for [`parse_bungeecord`]
+pub(crate) async fn read_bungeecord_handshake(
+    ctx: &mut ConnCtx,
+) -> io::Result<(Handshake, String)> {
+    let SBBungeeCordHandshake::Handshake(handshake) = ctx.read_unpublished().await?;
+    let server_address = handshake.server_address.to_inner();
+    let host = server_address.split('\0').next().unwrap_or_default();
+
+    let handshake = Handshake {
+        protocol_version: handshake.protocol_version,
+        server_address: BString::new(host.to_owned())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "server address too long"))?,
+        server_port: handshake.server_port,
+        next_state: handshake.next_state,
+    };
+
+    // Errors only if there are no subscribers, which is fine
+    let _ = ctx
+        .input
+        .send(ServerBound::Handshake(SBHandshake::Handshake(
+            handshake.clone(),
+        )));
+
+    Ok((handshake, server_address))
+}
+
+/// Parses BungeeCord forwarding data from the handshake server address
+///
+/// Returns `None` if it's missing or invalid
+pub(crate) fn parse_bungeecord(server_address: &str, username: &str) -> Option<Forwarded> {
+    // host\0ip\0uuid\0properties, where properties are optional
+    let mut parts = server_address.split('\0').skip(1);
+
+    let ip = parts.next()?.parse().ok()?;
+    let uuid = Uuid::parse_str(parts.next()?).ok()?;
+    let properties = match parts.next() {
+        Some(json) => auth::properties_from_json(serde_json::from_str(json).ok()?)?,
+        None => Vec::new(),
+    };
+
+    Some(Forwarded {
+        ip,
+        profile: GameProfile {
+            uuid,
+            name: username.to_string(),
+            properties,
+        },
+    })
+}
+
+/// Verifies and parses the response to a Velocity player info request
+///
+/// Returns `None` if the signature is invalid or the data is malformed
+pub(crate) fn parse_velocity(data: &[u8], secret: &[u8]) -> Option<Forwarded> {
+    // HMAC-SHA256 signature, followed by the signed data
+    if data.len() < 32 {
+        return None;
+    }
+    let (signature, mut data) = data.split_at(32);
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).ok()?;
+    mac.update(data);
+    mac.verify_slice(signature).ok()?;
+
+    let info = VelocityPlayerInfo::read_from(&mut data).ok()?;
+    if info.version.0 < VELOCITY_VERSION as i32 {
+        return None;
+    }
+
+    Some(Forwarded {
+        ip: info.address.parse().ok()?,
+        profile: GameProfile {
+            uuid: info.uuid,
+            name: info.username.to_inner(),
+            properties: info.properties,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_bungeecord, parse_velocity, SBBungeeCordHandshake};
+    use hmac::{Hmac, Mac};
+    use protocol::{
+        newtypes::NextState, packets::SBHandshake, FromBytesVersioned, ProtocolVersion, ToBytes,
+        VarInt,
+    };
+    use sha2::Sha256;
+    use uuid::Uuid;
+
+    #[test]
+    fn bungeecord() {
+        let address = "play.example.com\u{0}203.0.113.7\u{0}069a79f444e94726a5befca90e38aaf5\u{0}[{\"name\":\"textures\",\"value\":\"e30=\",\"signature\":\"c2ln\"}]";
+
+        let forwarded = parse_bungeecord(address, "Notch").unwrap();
+        assert_eq!(forwarded.ip.to_string(), "203.0.113.7");
+        assert_eq!(
+            forwarded.profile.uuid,
+            Uuid::parse_str("069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap()
+        );
+        assert_eq!(forwarded.profile.name, "Notch");
+        assert_eq!(*forwarded.profile.properties[0].value, "e30=");
+
+        // Not forwarded at all
+        assert!(parse_bungeecord("play.example.com", "Notch").is_none());
+    }
+
+    #[test]
+    fn bungeecord_handshake() {
+        let address = format!("play.example.com\0203.0.113.7\0{}", "a".repeat(300));
+
+        let mut data = Vec::new();
+        VarInt(0).write_to(&mut data).unwrap(); // packet id
+        VarInt(764).write_to(&mut data).unwrap();
+        address.clone().write_to(&mut data).unwrap();
+        25565u16.write_to(&mut data).unwrap();
+        NextState::Login.write_to(&mut data).unwrap();
+
+        // Too long for vanilla
+        assert!(SBHandshake::read_versioned(&mut &data[..], ProtocolVersion::V1_20_2).is_err());
+
+        let SBBungeeCordHandshake::Handshake(handshake) =
+            SBBungeeCordHandshake::read_versioned(&mut &data[..], ProtocolVersion::V1_20_2)
+                .unwrap();
+        assert_eq!(*handshake.server_address, address);
+    }
+
+    #[test]
+    fn velocity() {
+        let uuid = Uuid::parse_str("069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap();
+
+        let mut data = Vec::new();
+        VarInt(1).write_to(&mut data).unwrap();
+        "203.0.113.7".to_string().write_to(&mut data).unwrap();
+        uuid.write_to(&mut data).unwrap();
+        "Notch".to_string().write_to(&mut data).unwrap();
+        VarInt(0).write_to(&mut data).unwrap(); // no properties
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(&data);
+        let mut signed = mac.finalize().into_bytes().to_vec();
+        signed.extend_from_slice(&data);
+
+        let forwarded = parse_velocity(&signed, b"secret").unwrap();
+        assert_eq!(forwarded.ip.to_string(), "203.0.113.7");
+        assert_eq!(forwarded.profile.uuid, uuid);
+        assert_eq!(forwarded.profile.name, "Notch");
+
+        // Wrong secret
+        assert!(parse_velocity(&signed, b"not the secret").is_none());
+    }
+}
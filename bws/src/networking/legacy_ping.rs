@@ -2,9 +2,8 @@ use super::ConnCtx;
 use crate::Server;
 use std::{io, sync::Arc};
 use tokio::{
-    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
-    net::TcpStream,
-    task::{block_in_place, spawn_blocking},
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt},
+    task::block_in_place,
 };
 
 #[derive(Default, Debug)]
@@ -37,7 +36,12 @@ pub(crate) async fn handle(
     server: Arc<Server>,
     ctx: &mut ConnCtx,
 ) -> Result<bool, Box<dyn std::error::Error>> {
-    let (ping_type, payload) = match ctx.stream.fill_buf().await? {
+    // Bytes read while looking for a PROXY header, which can't start with 0xFE
+    if !ctx.stream.read_buffer().is_empty() {
+        return Ok(false);
+    }
+
+    let (ping_type, payload) = match ctx.stream.get_mut().fill_buf().await? {
         [0xFE] => (PingType::Pre1_4, Default::default()), // Legacy ping before 1.4
         [0xFE, 0x01] => (PingType::Pre1_6, Default::default()), // Legacy ping before 1.6
         [0xFE, 0x01, 0xFA, ..] => {
@@ -110,7 +114,7 @@ async fn write_pre_1_4(ctx: &mut ConnCtx, response: LegacyPingResponse) -> std::
     ctx.buf
         .extend(payload.encode_utf16().flat_map(|c| c.to_be_bytes())); // payload
 
-    ctx.stream.write_all(&ctx.buf).await
+    ctx.stream.get_mut().write_all(&ctx.buf).await
 }
 
 /// Writes response for 1.4-1.6 legacy ping
@@ -139,22 +143,22 @@ async fn write_pre_1_7(ctx: &mut ConnCtx, response: LegacyPingResponse) -> std::
 
     ctx.buf[1..3].copy_from_slice(&(chars as u16).to_be_bytes()); // Length in characters
 
-    ctx.stream.write_all(&ctx.buf).await
+    ctx.stream.get_mut().write_all(&ctx.buf).await
 }
 
 /// Reads payload from a 1.6 legacy ping
 async fn read_1_6(ctx: &mut ConnCtx) -> std::io::Result<LegacyPingPayload> {
     // consume first 27 bytes which are always the same
     ctx.buf.resize(27, 0);
-    ctx.stream.read_exact(&mut ctx.buf).await?;
+    ctx.stream.get_mut().read_exact(&mut ctx.buf).await?;
 
-    let hostname_len = ctx.stream.read_u16().await? - 7;
-    let protocol = ctx.stream.read_u8().await?;
+    let hostname_len = ctx.stream.get_mut().read_u16().await? - 7;
+    let protocol = ctx.stream.get_mut().read_u8().await?;
 
-    ctx.stream.read_u16().await?; // hostname length again...
+    ctx.stream.get_mut().read_u16().await?; // hostname length again...
 
     ctx.buf.resize(hostname_len as usize, 0);
-    ctx.stream.read_exact(&mut ctx.buf).await?;
+    ctx.stream.get_mut().read_exact(&mut ctx.buf).await?;
     let hostname = String::from_utf16_lossy(
         &ctx.buf
             .chunks(2)
@@ -162,7 +166,7 @@ async fn read_1_6(ctx: &mut ConnCtx) -> std::io::Result<LegacyPingPayload> {
             .collect::<Vec<_>>(),
     );
 
-    let port = ctx.stream.read_i32().await? as u16;
+    let port = ctx.stream.get_mut().read_i32().await? as u16;
 
     Ok(LegacyPingPayload {
         protocol,
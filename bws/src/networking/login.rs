@@ -0,0 +1,430 @@
+use super::{
+    forwarding::{self, Forwarded, Forwarding},
+    ConnCtx,
+};
+use crate::{
+    auth::{self, GameProfile},
+    Server,
+};
+use protocol::{
+    packets::{
+        handshake::Handshake,
+        login::{Disconnect, EncryptionRequest, LoginSuccess, PluginRequest, SetCompression},
+        CBLogin, SBLogin,
+    },
+    BString, ProtocolVersion, TextComponent, VarInt,
+};
+use rand::Rng;
+use std::{io, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::time::timeout;
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct PreLoginPayload {
+    pub username: String,
+    /// UUID sent by the client. Not verified in any way.
+    pub uuid: Option<Uuid>,
+    pub addr: SocketAddr,
+    /// Protocol version as sent in the handshake
+    pub protocol: i32,
+}
+
+/// Reason why a login failed
+#[derive(Debug, Clone)]
+pub enum LoginFailure {
+    /// Rejected by a `pre_login` or `authenticated` event handler
+    Rejected(Disconnect),
+    /// The client failed the encryption verification
+    Encryption,
+    /// The session server didn't authenticate the player
+    NotAuthenticated,
+    /// Couldn't reach the session server
+    SessionServerUnavailable,
+    /// Player information forwarded by the proxy was missing or invalid
+    Forwarding,
+}
+
+impl LoginFailure {
+    /// The disconnect packet sent to the client
+    fn disconnect(&self) -> Disconnect {
+        match self {
+            LoginFailure::Rejected(disconnect) => disconnect.clone(),
+            LoginFailure::Encryption => Disconnect {
+                reason: TextComponent::text("Encryption failed"),
+            },
+            LoginFailure::NotAuthenticated => Disconnect {
+                reason: TextComponent::text("Failed to verify username!"),
+            },
+            LoginFailure::SessionServerUnavailable => Disconnect {
+                reason: TextComponent::text(
+                    "Authentication servers are down. Please try again later, sorry!",
+                ),
+            },
+            LoginFailure::Forwarding => Disconnect {
+                reason: TextComponent::text("This server requires you to connect through a proxy"),
+            },
+        }
+    }
+}
+
+/// A connection in the login state, used for login plugin messaging
+pub struct LoginConn<'a> {
+    ctx: &'a mut ConnCtx,
+    next_message_id: i32,
+    /// Set if a request timed out, since the stream may be left in the middle of a packet
+    broken: bool,
+}
+
+impl<'a> LoginConn<'a> {
+    pub(crate) fn new(ctx: &'a mut ConnCtx) -> Self {
+        Self {
+            ctx,
+            next_message_id: 0,
+            broken: false,
+        }
+    }
+    pub fn id(&self) -> usize {
+        self.ctx.id
+    }
+    pub fn addr(&self) -> SocketAddr {
+        self.ctx.addr
+    }
+    /// Sends a login plugin request and waits for the client's response.
+    ///
+    /// Returns the response data, or `None` if the client didn't understand the request.
+    ///
+    /// If the timeout is reached, the connection can't be used anymore and the login will fail.
+    pub async fn plugin_request(
+        &mut self,
+        channel: &str,
+        data: Box<[u8]>,
+        timeout_after: Duration,
+    ) -> io::Result<Option<Box<[u8]>>> {
+        if self.broken {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "A previous login plugin request timed out",
+            ));
+        }
+
+        let message_id = self.next_message_id;
+        self.next_message_id += 1;
+
+        self.ctx
+            .write_packet(&CBLogin::PluginRequest(PluginRequest {
+                message_id: VarInt(message_id),
+                channel: channel.to_string(),
+                data,
+            }))
+            .await?;
+
+        let response = match timeout(timeout_after, self.ctx.read_packet()).await {
+            Ok(packet) => packet?,
+            Err(_) => {
+                self.broken = true;
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "Login plugin request timed out",
+                ));
+            }
+        };
+
+        match response {
+            SBLogin::PluginResponse(response) if response.message_id.0 == message_id => {
+                Ok(response.data)
+            }
+            _ => Err(unexpected_packet()),
+        }
+    }
+}
+
+/// Returns `true` if the login was successful and the connection should proceed to the
+/// configuration or play state
+///
+/// `bungeecord_address` is the full handshake server address when BungeeCord forwarding
+/// is enabled
+pub(crate) async fn handle(
+    server: &Arc<Server>,
+    ctx: &mut ConnCtx,
+    handshake: &Handshake,
+    bungeecord_address: Option<&str>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if !ctx.version.is_supported() {
+        debug!(
+            "{}: unsupported protocol version {}",
+            ctx.addr, ctx.version.0
+        );
+
+        ctx.write_packet(&CBLogin::Disconnect(Disconnect {
+            reason: TextComponent::translate(
+                "multiplayer.disconnect.incompatible",
+                vec![ProtocolVersion::supported_range().into()],
+            ),
+        }))
+        .await?;
+
+        return Ok(false);
+    }
+
+    let start = match ctx.read_packet().await? {
+        SBLogin::LoginStart(p) => p,
+        _ => return Err(unexpected_packet().into()),
+    };
+
+    let forwarded = match &server.options.forwarding {
+        Forwarding::None => None,
+        Forwarding::BungeeCord => bungeecord_address
+            .and_then(|address| forwarding::parse_bungeecord(address, &start.name)),
+        Forwarding::Velocity { secret } => velocity_forwarding(ctx, secret).await?,
+    };
+
+    if let Some(forwarded) = &forwarded {
+        let port = ctx.addr.port();
+        ctx.set_addr(server, SocketAddr::new(forwarded.ip, port))
+            .await;
+    } else if server.options.forwarding != Forwarding::None {
+        debug!("{}: missing or invalid forwarding data", ctx.addr);
+        reject(server, ctx, &start.name, LoginFailure::Forwarding).await?;
+        return Ok(false);
+    }
+
+    let payload = PreLoginPayload {
+        username: start.name.to_string(),
+        uuid: start.uuid,
+        addr: ctx.addr,
+        protocol: handshake.protocol_version.0,
+    };
+
+    let mut rejection = None;
+
+    for handler in &server.global_events.pre_login {
+        handler(server.clone(), ctx.id, &payload, &mut rejection);
+    }
+
+    if let Some(rejection) = rejection {
+        reject(server, ctx, &start.name, LoginFailure::Rejected(rejection)).await?;
+        return Ok(false);
+    }
+
+    let mut profile = if let Some(forwarded) = forwarded {
+        forwarded.profile
+    } else if server.options.online_mode {
+        let Some(shared_secret) = enable_encryption(server, ctx, &start.name).await? else {
+            return Ok(false);
+        };
+
+        let Some(profile) = authenticate(server, ctx, &start.name, &shared_secret).await? else {
+            return Ok(false);
+        };
+
+        profile
+    } else {
+        GameProfile {
+            uuid: auth::offline_uuid(&start.name),
+            name: start.name.to_string(),
+            properties: Vec::new(),
+        }
+    };
+
+    let mut conn = LoginConn::new(ctx);
+    let mut rejection = None;
+    for handler in &server.global_events.login_plugin {
+        rejection = handler(server.clone(), &mut conn).await;
+
+        if rejection.is_some() || conn.broken {
+            break;
+        }
+    }
+    if conn.broken {
+        return Err(
+            io::Error::new(io::ErrorKind::TimedOut, "Login plugin request timed out").into(),
+        );
+    }
+    if let Some(rejection) = rejection {
+        reject(
+            server,
+            ctx,
+            &profile.name,
+            LoginFailure::Rejected(rejection),
+        )
+        .await?;
+        return Ok(false);
+    }
+
+    let mut rejection = None;
+
+    for handler in &server.global_events.authenticated {
+        handler(server.clone(), ctx.id, &mut profile, &mut rejection);
+    }
+
+    if let Some(rejection) = rejection {
+        reject(
+            server,
+            ctx,
+            &profile.name,
+            LoginFailure::Rejected(rejection),
+        )
+        .await?;
+        return Ok(false);
+    }
+
+    if let Some(threshold) = server.options.compression_threshold {
+        ctx.write_packet(&CBLogin::SetCompression(SetCompression {
+            threshold: VarInt(threshold.min(i32::MAX as usize) as i32),
+        }))
+        .await?;
+
+        ctx.stream.codec_mut().enable_compression(threshold);
+    }
+
+    let username = BString::new(profile.name).ok_or("Username too long")?;
+
+    ctx.write_packet(&CBLogin::LoginSuccess(LoginSuccess {
+        uuid: profile.uuid,
+        username: username.clone(),
+        properties: profile.properties,
+    }))
+    .await?;
+
+    // Since 1.20.2 the client acknowledges the login before switching to the configuration state
+    if ctx.version >= ProtocolVersion::V1_20_2 {
+        match ctx.read_packet().await? {
+            SBLogin::LoginAcknowledged => {}
+            _ => return Err(unexpected_packet().into()),
+        }
+    }
+
+    info!(
+        "{} ({}) logged in from {}",
+        *username, profile.uuid, ctx.addr
+    );
+
+    Ok(true)
+}
+
+/// Requests the player information from Velocity
+///
+/// Returns `None` if the proxy didn't send valid information
+async fn velocity_forwarding(ctx: &mut ConnCtx, secret: &[u8]) -> io::Result<Option<Forwarded>> {
+    let response = LoginConn::new(ctx)
+        .plugin_request(
+            forwarding::VELOCITY_CHANNEL,
+            Box::new([forwarding::VELOCITY_VERSION]),
+            Duration::from_secs(5),
+        )
+        .await?;
+
+    Ok(response.and_then(|data| forwarding::parse_velocity(&data, secret)))
+}
+
+/// Performs the encryption request/response exchange and enables encryption on the stream
+///
+/// Returns the shared secret, or `None` if the client failed verification and was disconnected
+async fn enable_encryption(
+    server: &Arc<Server>,
+    ctx: &mut ConnCtx,
+    username: &str,
+) -> io::Result<Option<[u8; 16]>> {
+    let mut verify_token = [0u8; 4];
+    rand::thread_rng().fill(&mut verify_token[..]);
+
+    ctx.write_packet(&CBLogin::EncryptionRequest(EncryptionRequest {
+        // Always empty since 1.7
+        server_id: BString::new(String::new()).unwrap(),
+        public_key: server.rsa_public_key.clone(),
+        verify_token: verify_token.to_vec(),
+    }))
+    .await?;
+
+    let response = match ctx.read_packet().await? {
+        SBLogin::EncryptionResponse(p) => p,
+        _ => return Err(unexpected_packet()),
+    };
+
+    let decrypted_token = server
+        .rsa_keypair
+        .decrypt(rsa::Pkcs1v15Encrypt, &response.verify_token);
+    if decrypted_token.as_deref() != Ok(&verify_token[..]) {
+        debug!("{}: verify token incorrect", ctx.addr);
+        reject(server, ctx, username, LoginFailure::Encryption).await?;
+        return Ok(None);
+    }
+
+    let shared_secret = server
+        .rsa_keypair
+        .decrypt(rsa::Pkcs1v15Encrypt, &response.shared_secret)
+        .ok()
+        .and_then(|secret| <[u8; 16]>::try_from(secret).ok());
+    let Some(shared_secret) = shared_secret else {
+        debug!("{}: invalid shared secret", ctx.addr);
+        reject(server, ctx, username, LoginFailure::Encryption).await?;
+        return Ok(None);
+    };
+
+    ctx.enable_encryption(&shared_secret);
+
+    Ok(Some(shared_secret))
+}
+
+/// Authenticates the player with the session server
+///
+/// Returns `None` if authentication failed and the client was disconnected
+async fn authenticate(
+    server: &Arc<Server>,
+    ctx: &mut ConnCtx,
+    username: &str,
+    shared_secret: &[u8],
+) -> io::Result<Option<GameProfile>> {
+    let hash = auth::server_hash("", shared_secret, &server.rsa_public_key);
+
+    let failure = match auth::has_joined(
+        &server.http_client,
+        &server.options.session_server,
+        username,
+        &hash,
+    )
+    .await
+    {
+        Ok(Some(profile)) => return Ok(Some(profile)),
+        Ok(None) => {
+            debug!("{}: failed to verify username {username}", ctx.addr);
+            LoginFailure::NotAuthenticated
+        }
+        Err(e) => {
+            error!("Couldn't verify username {username}: {e}");
+            LoginFailure::SessionServerUnavailable
+        }
+    };
+
+    reject(server, ctx, username, failure).await?;
+
+    Ok(None)
+}
+
+/// Notifies the `login_failed` event handlers and disconnects the client
+async fn reject(
+    server: &Arc<Server>,
+    ctx: &mut ConnCtx,
+    username: &str,
+    failure: LoginFailure,
+) -> io::Result<()> {
+    for handler in &server.global_events.login_failed {
+        handler(server.clone(), ctx.id, username, &failure);
+    }
+
+    ctx.write_packet(&CBLogin::Disconnect(failure.disconnect()))
+        .await
+}
+
+/// Sends a disconnect packet with the given reason
+pub(crate) async fn disconnect(ctx: &mut ConnCtx, reason: &str) -> io::Result<()> {
+    ctx.write_packet(&CBLogin::Disconnect(Disconnect {
+        reason: TextComponent::text(reason),
+    }))
+    .await
+}
+
+fn unexpected_packet() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "Client not following format")
+}
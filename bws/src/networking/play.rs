@@ -0,0 +1,58 @@
+use super::ConnCtx;
+use crate::Server;
+use futures::{SinkExt, StreamExt};
+use protocol::{
+    limits::{with_limits, DecodeLimits},
+    packets::{SBPlay, ServerBound},
+    version::ForVersion,
+    FromBytesVersioned, ToBytesVersioned,
+};
+use std::sync::Arc;
+use tokio::select;
+use tracing::debug;
+
+/// Pumps packets between the stream and the connection's input/output channels
+pub(crate) async fn handle(
+    server: &Arc<Server>,
+    ctx: &mut ConnCtx,
+) -> Result<(), Box<dyn std::error::Error>> {
+    loop {
+        // Reading and writing frames is cancel safe
+        select! {
+            frame = ctx.stream.next() => {
+                // Connection closed by the client
+                let Some(frame) = frame.transpose()? else {
+                    return Ok(());
+                };
+
+                let mut slice = &frame[..];
+                let packet = with_limits(DecodeLimits::for_frame(frame.len()), || {
+                    SBPlay::read_versioned(&mut slice, ctx.version)
+                });
+
+                match packet {
+                    // Errors only if there are no subscribers, which is fine
+                    Ok(packet) => {
+                        let _ = ctx.input.send(ServerBound::Play(packet));
+                    }
+                    // Not all play packets are mapped yet
+                    Err(e) => debug!("Ignoring play packet from {}: {}", ctx.addr, e),
+                }
+            }
+            packet = ctx.output.recv() => {
+                let Some(packet) = packet else {
+                    return Ok(());
+                };
+
+                // Not all play packets are mapped for every version
+                if !packet.is_available(ctx.version) {
+                    debug!("Not sending unmapped packet to {}: {:?}", ctx.addr, packet);
+                    continue;
+                }
+
+                ctx.stream.send(ForVersion(&packet, ctx.version)).await?;
+            }
+            _ = server.graceful_exit.wait_for_exit() => return Ok(()),
+        }
+    }
+}
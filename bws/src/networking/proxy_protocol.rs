@@ -0,0 +1,256 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+const V1_SIGNATURE: &[u8] = b"PROXY ";
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+/// Maximum length of a v1 header, including the CRLF
+const V1_MAX_LENGTH: u64 = 107;
+/// How long to wait for the header before dropping the connection
+pub(crate) const HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// HAProxy PROXY protocol support, for when the server is behind a TCP load balancer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProxyProtocol {
+    #[default]
+    Disabled,
+    /// Accept connections both with and without a PROXY header
+    Optional,
+    /// Reject connections without a PROXY header
+    Required,
+}
+
+const SIGNATURES: [&[u8]; 2] = [V1_SIGNATURE, V2_SIGNATURE];
+
+/// What a connection starts with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Header {
+    /// A PROXY header, with the address of the client unless it was a `LOCAL`/`UNKNOWN`
+    /// connection
+    Proxy(Option<SocketAddr>),
+    /// No PROXY header, with the bytes that were read while looking for one, which are the
+    /// start of the first packet
+    Missing(Vec<u8>),
+}
+
+/// Reads a PROXY protocol (v1 or v2) header, if there is one
+pub(crate) async fn read_header<R: AsyncBufRead + Unpin>(
+    stream: &mut R,
+    mode: ProxyProtocol,
+) -> io::Result<Header> {
+    // A read can return just a part of the signature, so the bytes are taken one at a time
+    // for as long as they can still be the start of one
+    let mut read = Vec::new();
+    let signature = loop {
+        if let Some(signature) = SIGNATURES.into_iter().find(|s| *s == read) {
+            break Some(signature);
+        }
+
+        let Some(&byte) = stream.fill_buf().await?.first() else {
+            break None;
+        };
+        read.push(byte);
+        if !SIGNATURES.iter().any(|s| s.starts_with(&read)) {
+            read.pop();
+            break None;
+        }
+        stream.consume(1);
+    };
+
+    match signature {
+        Some(signature) if signature == V1_SIGNATURE => read_v1(stream).await.map(Header::Proxy),
+        Some(_) => read_v2(stream).await.map(Header::Proxy),
+        None if mode == ProxyProtocol::Required => Err(invalid("Missing PROXY protocol header")),
+        None => Ok(Header::Missing(read)),
+    }
+}
+
+/// Reads the rest of a human-readable v1 header
+async fn read_v1<R: AsyncBufRead + Unpin>(stream: &mut R) -> io::Result<Option<SocketAddr>> {
+    let mut line = Vec::new();
+    (&mut *stream)
+        .take(V1_MAX_LENGTH - V1_SIGNATURE.len() as u64)
+        .read_until(b'\n', &mut line)
+        .await?;
+
+    let line = line
+        .strip_suffix(b"\r\n")
+        .ok_or_else(|| invalid("PROXY v1 header too long"))?;
+    let line = std::str::from_utf8(line).map_err(|_| invalid("PROXY v1 header not ASCII"))?;
+
+    // PROXY <TCP4|TCP6|UNKNOWN> <source ip> <destination ip> <source port> <destination port>
+    let mut parts = line.split(' ');
+
+    match parts.next() {
+        Some("TCP4") | Some("TCP6") => {}
+        Some("UNKNOWN") => return Ok(None),
+        _ => return Err(invalid("Invalid PROXY v1 protocol")),
+    }
+
+    let ip: IpAddr = parts
+        .next()
+        .and_then(|ip| ip.parse().ok())
+        .ok_or_else(|| invalid("Invalid PROXY v1 source address"))?;
+    let port: u16 = parts
+        .nth(1)
+        .and_then(|port| port.parse().ok())
+        .ok_or_else(|| invalid("Invalid PROXY v1 source port"))?;
+
+    Ok(Some(SocketAddr::new(ip, port)))
+}
+
+/// Reads the rest of a binary v2 header
+async fn read_v2<R: AsyncBufRead + Unpin>(stream: &mut R) -> io::Result<Option<SocketAddr>> {
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await?;
+
+    let version = header[0] >> 4;
+    let command = header[0] & 0x0F;
+    let family = header[1];
+    let length = u16::from_be_bytes([header[2], header[3]]);
+
+    if version != 2 {
+        return Err(invalid("Unsupported PROXY protocol version"));
+    }
+
+    // Addresses and TLVs, which are not used
+    let mut data = vec![0u8; length as usize];
+    stream.read_exact(&mut data).await?;
+
+    match command {
+        // LOCAL, the connection was made by the proxy itself
+        0x0 => return Ok(None),
+        // PROXY
+        0x1 => {}
+        _ => return Err(invalid("Invalid PROXY v2 command")),
+    }
+
+    // Only TCP over IPv4 and IPv6 carry an address we can use
+    let addr = match family {
+        0x11 if data.len() >= 12 => {
+            let ip: [u8; 4] = data[..4].try_into().unwrap();
+            let port = u16::from_be_bytes([data[8], data[9]]);
+
+            SocketAddr::new(Ipv4Addr::from(ip).into(), port)
+        }
+        0x21 if data.len() >= 36 => {
+            let ip: [u8; 16] = data[..16].try_into().unwrap();
+            let port = u16::from_be_bytes([data[32], data[33]]);
+
+            SocketAddr::new(Ipv6Addr::from(ip).into(), port)
+        }
+        0x11 | 0x21 => return Err(invalid("PROXY v2 address block too short")),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(addr))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_header, Header, ProxyProtocol};
+    use std::{
+        io,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+    use tokio::io::{AsyncBufRead, AsyncRead, AsyncReadExt, BufReader, ReadBuf};
+
+    /// Returns a single byte per read, like a slow connection
+    struct OneByte<'a>(&'a [u8]);
+
+    impl AsyncRead for OneByte<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            if let Some((first, rest)) = self.0.split_first() {
+                buf.put_slice(&[*first]);
+                self.0 = rest;
+            }
+
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Reads the header, and checks that it's followed by the rest of the stream
+    async fn check(
+        mut stream: impl AsyncBufRead + Unpin,
+        mode: ProxyProtocol,
+        rest: &[u8],
+    ) -> Header {
+        let header = read_header(&mut stream, mode).await.unwrap();
+
+        let mut read = match &header {
+            Header::Proxy(_) => Vec::new(),
+            Header::Missing(read) => read.clone(),
+        };
+        stream.read_to_end(&mut read).await.unwrap();
+        assert_eq!(read, rest);
+
+        header
+    }
+
+    #[tokio::test]
+    async fn proxy_protocol_headers() {
+        let mut v2 = b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x0C".to_vec();
+        v2.extend_from_slice(&[203, 0, 113, 7, 10, 0, 0, 1, 0x63, 0xDD, 0x63, 0xDD]);
+        v2.push(0xFE);
+
+        let samples: &[(&[u8], ProxyProtocol, Option<&str>)] = &[
+            (
+                b"PROXY TCP4 203.0.113.7 10.0.0.1 25565 25565\r\n\xFE",
+                ProxyProtocol::Required,
+                Some("203.0.113.7:25565"),
+            ),
+            (
+                b"PROXY TCP6 2001:db8::1 2001:db8::2 51000 25565\r\n\xFE",
+                ProxyProtocol::Optional,
+                Some("[2001:db8::1]:51000"),
+            ),
+            (b"PROXY UNKNOWN\r\n\xFE", ProxyProtocol::Required, None),
+            (&v2, ProxyProtocol::Required, Some("203.0.113.7:25565")),
+        ];
+
+        for (bytes, mode, addr) in samples {
+            for header in [
+                check(BufReader::new(*bytes), *mode, b"\xFE").await,
+                check(BufReader::new(OneByte(bytes)), *mode, b"\xFE").await,
+            ] {
+                let Header::Proxy(read) = header else {
+                    panic!("Missing header in {bytes:?}");
+                };
+                assert_eq!(read.map(|a| a.to_string()).as_deref(), *addr);
+            }
+        }
+
+        // Handshakes that start like a signature, as their length is 0x50 or 0x0D
+        for bytes in [&b"\x50\x00\xFE"[..], b"\x0D\x00\xFE", b"\xFE"] {
+            for header in [
+                check(BufReader::new(bytes), ProxyProtocol::Optional, bytes).await,
+                check(
+                    BufReader::new(OneByte(bytes)),
+                    ProxyProtocol::Optional,
+                    bytes,
+                )
+                .await,
+            ] {
+                assert!(matches!(header, Header::Missing(_)));
+            }
+        }
+
+        // Required, but missing
+        let mut stream = BufReader::new(&b"\x50\x00"[..]);
+        assert!(read_header(&mut stream, ProxyProtocol::Required)
+            .await
+            .is_err());
+    }
+}
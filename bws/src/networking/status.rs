@@ -0,0 +1,74 @@
+use super::ConnCtx;
+use crate::Server;
+use protocol::packets::{
+    handshake::Handshake,
+    status::{PingResponse, SBStatus, StatusResponse},
+    CBStatus,
+};
+use std::{net::SocketAddr, sync::Arc};
+use tracing::{error, trace};
+
+#[derive(Debug, Clone)]
+pub struct StatusPayload {
+    pub addr: SocketAddr,
+    /// Protocol version as sent in the handshake
+    pub protocol: i32,
+    /// The hostname the client used to connect
+    pub hostname: String,
+    pub port: u16,
+}
+
+pub(crate) async fn handle(
+    server: &Arc<Server>,
+    ctx: &mut ConnCtx,
+    handshake: &Handshake,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let payload = StatusPayload {
+        addr: ctx.addr,
+        protocol: handshake.protocol_version.0,
+        hostname: handshake.server_address.to_string(),
+        port: handshake.server_port,
+    };
+
+    loop {
+        match ctx.read_packet().await? {
+            SBStatus::StatusRequest => {
+                let mut response = None;
+
+                for handler in &server.global_events.status {
+                    handler(server.clone(), ctx.id, &payload, &mut response);
+                }
+
+                let Some(response) = response else {
+                    // Not responding makes the server appear offline
+                    return Ok(());
+                };
+
+                trace!("Sending StatusResponse: {response:?}");
+
+                write_response(ctx, response).await?;
+            }
+            SBStatus::PingRequest(request) => {
+                trace!("Sending PingResponse: {request:?}");
+
+                ctx.write_packet(&CBStatus::PingResponse(PingResponse {
+                    payload: request.payload,
+                }))
+                .await?;
+
+                // Ping is always the last packet of the status sequence
+                return Ok(());
+            }
+        }
+    }
+}
+
+async fn write_response(ctx: &mut ConnCtx, response: StatusResponse) -> std::io::Result<()> {
+    // Status response JSON payload can't be longer than 32767 characters (not bytes).
+    // Validate length to help debug invalid packets
+    if response.json.to_string().chars().count() > 32767 {
+        error!("Sending invalid status response: too long.\n{:?}", response);
+    }
+
+    ctx.write_packet(&CBStatus::StatusResponse(response)).await
+}
@@ -1,4 +1,7 @@
-use bws::{LegacyPingPayload, LegacyPingResponse, Server};
+use bws::{
+    protocol::packets::status::{StatusResponse, StatusResponseBuilder},
+    LegacyPingPayload, LegacyPingResponse, Server, StatusPayload,
+};
 use std::sync::Arc;
 use tokio::net::TcpListener;
 use tracing_subscriber::{filter::LevelFilter, EnvFilter};
@@ -17,6 +20,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut server = Server::new();
 
     server.global_events.legacy_ping.push(legacy_ping);
+    server.global_events.status.push(status);
 
     server
         .run(TcpListener::bind(("127.0.0.1", 25565)).await?)
@@ -24,9 +28,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 fn legacy_ping(
-    server: Arc<Server>,
-    id: usize,
-    payload: &LegacyPingPayload,
+    _server: Arc<Server>,
+    _id: usize,
+    _payload: &LegacyPingPayload,
     response: &mut Option<LegacyPingResponse>,
 ) {
     *response = Some(LegacyPingResponse {
@@ -37,3 +41,17 @@ fn legacy_ping(
         version: "".to_string(),
     })
 }
+
+fn status(
+    _server: Arc<Server>,
+    _id: usize,
+    payload: &StatusPayload,
+    response: &mut Option<StatusResponse>,
+) {
+    *response = Some(
+        StatusResponseBuilder::new("bws".to_string(), payload.protocol)
+            .players(0, 10, Vec::new())
+            .description("a".to_string())
+            .build(),
+    )
+}
@@ -27,7 +27,7 @@ impl GracefulExit {
         }
     }
     /// Creates a guard that prevents shutdown while it's in scope, or until a timeout is reached
-    pub fn guard(&self) -> GracefulExitGuard {
+    pub fn guard(&self) -> GracefulExitGuard<'_> {
         // increase counter
         *self.active_guards.0.lock().unwrap() += 1;
 
@@ -78,6 +78,12 @@ impl GracefulExit {
     }
 }
 
+impl Default for GracefulExit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<'a> Drop for GracefulExitGuard<'a> {
     fn drop(&mut self) {
         // decrease guards counter
@@ -1,48 +1,75 @@
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
-use proc_macro2::{Span, TokenStream as TokenStream2};
+use proc_macro2::{Span, TokenStream as TokenStream2, TokenTree};
 use quote::quote;
 use syn::spanned::Spanned;
 use syn::{
     parse_macro_input, Attribute, Data, DeriveInput, Expr, ExprLit, Fields, Index, Lit, Path,
 };
 
-#[proc_macro_derive(ToBytes, attributes(discriminant_as))]
+#[proc_macro_derive(
+    ToBytes,
+    attributes(
+        discriminant_as,
+        prefixed,
+        varint,
+        varlong,
+        angle,
+        fixed_point,
+        skip,
+        when,
+        max_len
+    )
+)]
 pub fn derive_tobytes(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
     let name = input.ident;
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
-    let implementation = match input.data {
+    let (implementation, size_implementation) = match input.data {
         Data::Struct(data) => match data.fields {
-            Fields::Named(fields) => {
-                let field_names = fields.named.iter().map(|f| &f.ident);
-
-                quote! {
-                    let mut written = 0;
-                    #(written += ToBytes::write_to(&self.#field_names, write)?; )*
-                    Ok(written)
-                }
+            Fields::Named(_) | Fields::Unnamed(_) => {
+                let values: Vec<_> = data
+                    .fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, f)| match &f.ident {
+                        Some(name) => quote! { &self.#name },
+                        None => {
+                            let index = Index::from(i);
+                            quote! { &self.#index }
+                        }
+                    })
+                    .collect();
+                let (writes, sizes) = write_fields(&data.fields, &values);
+
+                (
+                    quote! {
+                        let mut written = 0;
+                        #( #writes )*
+                        Ok(written)
+                    },
+                    quote! {
+                        0 #(+ #sizes )*
+                    },
+                )
             }
-            Fields::Unnamed(fields) => {
-                let field_indices = (0..fields.unnamed.len()).map(|i| Index::from(i));
-
+            Fields::Unit => (
                 quote! {
-                    let mut written = 0;
-                    #(written += ToBytes::write_to(&self.#field_indices, write)?; )*
-                    Ok(written)
-                }
-            }
-            Fields::Unit => quote! {
-                Ok(0)
-            },
+                    Ok(0)
+                },
+                quote! {
+                    0
+                },
+            ),
         },
         Data::Enum(data) => {
             let discriminant_type = get_discriminant(input.attrs);
 
             let mut match_arms = Vec::new();
+            let mut size_match_arms = Vec::new();
 
             let mut next_discriminant = 0;
 
@@ -65,53 +92,71 @@ pub fn derive_tobytes(input: TokenStream) -> TokenStream {
                         write
                     )?;
                 };
+                let discriminant_size = quote! {
+                    ToBytes::encoded_size(
+                        &(::std::convert::Into::< #discriminant_type >::into(#next_discriminant) )
+                    )
+                };
 
-                match_arms.push(match variant.fields {
+                let (pattern, field_names) = match &variant.fields {
                     Fields::Named(fields) => {
-                        let field_names = fields.named.iter().map(|f| &f.ident);
-                        let field_names2 = field_names.clone();
-
-                        quote! {
-                            Self::#variant_name { #( #field_names ),*} => {
-                               #discriminant
-                               #(written += ToBytes::write_to( #field_names2, write)?; )*
-                            }
-                        }
+                        let field_names: Vec<_> = fields
+                            .named
+                            .iter()
+                            .map(|f| f.ident.clone().unwrap())
+                            .collect();
+
+                        (
+                            quote! { Self::#variant_name { #( #field_names ),* } },
+                            field_names,
+                        )
                     }
                     Fields::Unnamed(fields) => {
-                        let field_names = fields.unnamed.iter().enumerate().map(|(i, _)| {
-                            syn::Ident::new(&format!("__field{}", i), Span::call_site())
-                        });
-                        let field_names2 = field_names.clone();
-
-                        quote! {
-                            Self::#variant_name ( #( #field_names ),*) => {
-                               #discriminant
-                               #(written += ToBytes::write_to( #field_names2, write)?; )*
-                            }
-                        }
+                        let field_names: Vec<_> = (0..fields.unnamed.len())
+                            .map(|i| syn::Ident::new(&format!("__field{}", i), Span::call_site()))
+                            .collect();
+
+                        (
+                            quote! { Self::#variant_name ( #( #field_names ),* ) },
+                            field_names,
+                        )
                     }
-                    Fields::Unit => {
-                        quote! {
-                            Self::#variant_name => {
-                               #discriminant
-                            }
-                        }
+                    Fields::Unit => (quote! { Self::#variant_name }, Vec::new()),
+                };
+                let values: Vec<_> = field_names.iter().map(|n| quote! { #n }).collect();
+                let (writes, sizes) = write_fields(&variant.fields, &values);
+
+                match_arms.push(quote! {
+                    #[allow(unused_variables)]
+                    #pattern => {
+                       #discriminant
+                       #( #writes )*
                     }
                 });
+                size_match_arms.push(quote! {
+                    #[allow(unused_variables)]
+                    #pattern => #discriminant_size #(+ #sizes )*
+                });
 
                 next_discriminant += 1;
             }
 
-            quote! {
-                let mut written = 0;
+            (
+                quote! {
+                    let mut written = 0;
 
-                match self {
-                    #(#match_arms,)*
-                }
+                    match self {
+                        #(#match_arms,)*
+                    }
 
-                Ok(written)
-            }
+                    Ok(written)
+                },
+                quote! {
+                    match self {
+                        #(#size_match_arms,)*
+                    }
+                },
+            )
         }
         Data::Union(_) => {
             return syn::Error::new(Span::call_site(), "ToBytes can't be derived for Unions")
@@ -125,36 +170,114 @@ pub fn derive_tobytes(input: TokenStream) -> TokenStream {
             fn write_to<__W: ::std::io::Write>(&self, write: &mut __W) -> ::std::io::Result<usize> {
                 #implementation
             }
+            fn encoded_size(&self) -> usize {
+                #size_implementation
+            }
         }
     };
 
     TokenStream::from(expanded)
 }
 
-#[proc_macro_derive(FromBytes, attributes(discriminant_as))]
+#[proc_macro_derive(
+    FromBytes,
+    attributes(
+        discriminant_as,
+        prefixed,
+        varint,
+        varlong,
+        angle,
+        fixed_point,
+        skip,
+        when,
+        max_len
+    )
+)]
 pub fn derive_from_bytes(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
     let name = input.ident;
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
+    // Types with a lifetime borrow from the packet buffer, and can only be read from a slice.
+    // Only their fields that use the lifetime are read borrowed, so the rest can be of any
+    // `FromBytes` type.
+    let borrowed_lifetime = input.generics.lifetimes().next().map(|l| &l.lifetime);
+    let read_fn = |ty: &syn::Type| match borrowed_lifetime {
+        Some(lifetime) if uses_lifetime(quote! { #ty }, lifetime) => {
+            quote! { protocol::FromBytesBorrowed::read_borrowed }
+        }
+        _ => quote! { FromBytes::read_from },
+    };
+
+    let type_name = name.to_string();
+    let read_field = |f: &syn::Field,
+                      field: Option<String>,
+                      variant: Option<&syn::Ident>,
+                      scope: &TokenStream2| {
+        let mut read = FieldEncoding::read(f, &read_fn(&f.ty), scope);
+
+        if let Some(max_len) = get_max_len(&f.attrs) {
+            read = quote! { protocol::limits::with_field_limit(#max_len, || #read) };
+        }
+
+        if let Some(field) = field {
+            read = quote! {
+                #read.map_err(|e| protocol::DecodeError::in_field(e, #type_name, #field))
+            };
+        }
+        if let Some(variant) = variant {
+            let variant = variant.to_string();
+            read = quote! {
+                #read.map_err(|e| protocol::DecodeError::in_variant(e, #type_name, #variant))
+            };
+        }
+
+        read
+    };
+    // Newtypes are transparent in the field path
+    let unnamed_field = |i: usize, count: usize| (count > 1).then(|| i.to_string());
+    // Named fields are read into variables first, so that conditions can refer to them
+    let read_named = |fields: &syn::FieldsNamed, variant: Option<&syn::Ident>| {
+        let names: Vec<_> = fields.named.iter().flat_map(|f| &f.ident).collect();
+        let bindings: Vec<_> = names
+            .iter()
+            .map(|name| syn::Ident::new(&format!("__{name}"), name.span()))
+            .collect();
+        let references: Vec<_> = bindings.iter().map(|b| quote! { &#b }).collect();
+        let types = fields.named.iter().map(|f| &f.ty);
+        let reads = fields.named.iter().enumerate().map(|(i, f)| {
+            let scope = field_scope(&names[..i], &references[..i]);
+            read_field(f, Some(names[i].to_string()), variant, &scope)
+        });
+        let path = match variant {
+            Some(variant) => quote! { Self::#variant },
+            None => quote! { Self },
+        };
+
+        quote! {
+            #( let #bindings: #types = #reads?; )*
+
+            Ok(#path { #( #names: #bindings, )* })
+        }
+    };
+
     let implementation = match input.data {
         Data::Struct(data) => match data.fields {
-            Fields::Named(fields) => {
-                let field_names = fields.named.iter().map(|f| &f.ident);
-
-                quote! {
-                    Ok(Self{
-                        #( #field_names: FromBytes::read_from(read)?, )*
-                    })
-                }
-            }
+            Fields::Named(fields) => read_named(&fields, None),
             Fields::Unnamed(fields) => {
                 let field_types = fields.unnamed.iter().map(|f| &f.ty);
+                let count = fields.unnamed.len();
+                let reads = fields
+                    .unnamed
+                    .iter()
+                    .enumerate()
+                    .map(|(i, f)| read_field(f, unnamed_field(i, count), None, &quote! {}));
+
                 quote! {
                     Ok(Self (
                         #({
-                            let temp: #field_types = FromBytes::read_from(read)?;
+                            let temp: #field_types = #reads?;
                             temp
                         },)*
                     ))
@@ -185,27 +308,29 @@ pub fn derive_from_bytes(input: TokenStream) -> TokenStream {
 
                 match variant.fields {
                     Fields::Named(fields) => {
-                        let field_names = fields.named.iter().map(|f| &f.ident);
+                        let read = read_named(&fields, Some(&variant_name));
 
                         match_arms.push(quote! {
                             #next_discriminant => {
-                                Ok(Self::#variant_name{ #(
-                                    #field_names: FromBytes::read_from(read)?,
-                                )* })
+                                #read
                             }
                         });
                     }
                     Fields::Unnamed(fields) => {
                         let field_types = fields.unnamed.iter().map(|f| &f.ty);
+                        let count = fields.unnamed.len();
+                        let reads = fields.unnamed.iter().enumerate().map(|(i, f)| {
+                            read_field(f, unnamed_field(i, count), Some(&variant_name), &quote! {})
+                        });
 
                         match_arms.push(quote! {
                             #next_discriminant => {
                                 Ok(Self::#variant_name ( #(
                                     {
-                                        let temp: #field_types = FromBytes::read_from(read)?;
+                                        let temp: #field_types = #reads?;
                                         temp
                                     }
-                                )* ))
+                                ),* ))
                             }
                         });
                     }
@@ -227,7 +352,7 @@ pub fn derive_from_bytes(input: TokenStream) -> TokenStream {
 
                 match discriminant {
                     #(#match_arms,)*
-                    _ => Err(::std::io::Error::new(::std::io::ErrorKind::InvalidData, "Invalid enum discriminant")),
+                    _ => Err(protocol::DecodeError::invalid_discriminant(#type_name, discriminant)),
                 }
             }
         }
@@ -238,29 +363,57 @@ pub fn derive_from_bytes(input: TokenStream) -> TokenStream {
         }
     };
 
-    let expanded = quote! {
-        impl #impl_generics FromBytes for #name #ty_generics #where_clause {
-            fn read_from<__R: ::std::io::Read>(read: &mut __R) -> ::std::io::Result<Self> {
-                #implementation
+    let expanded = match borrowed_lifetime {
+        Some(lifetime) => quote! {
+            impl #impl_generics protocol::FromBytesBorrowed<#lifetime> for #name #ty_generics #where_clause {
+                fn read_borrowed(read: &mut &#lifetime [u8]) -> ::std::io::Result<Self> {
+                    #implementation
+                }
             }
-        }
+        },
+        None => quote! {
+            impl #impl_generics FromBytes for #name #ty_generics #where_clause {
+                fn read_from<__R: ::std::io::Read>(read: &mut __R) -> ::std::io::Result<Self> {
+                    #implementation
+                }
+            }
+        },
     };
 
     TokenStream::from(expanded)
 }
 
+/// Whether `tokens` contain `lifetime`
+fn uses_lifetime(tokens: TokenStream2, lifetime: &syn::Lifetime) -> bool {
+    let mut tokens = tokens.into_iter().peekable();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Group(group) if uses_lifetime(group.stream(), lifetime) => return true,
+            TokenTree::Punct(punct) if punct.as_char() == '\'' => {
+                if matches!(tokens.peek(), Some(TokenTree::Ident(ident)) if *ident == lifetime.ident)
+                {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    false
+}
+
 fn get_discriminant(attrs: Vec<Attribute>) -> TokenStream2 {
     for attribute in attrs {
         if let Some(ident) = attribute.path.get_ident() {
-            if ident.to_string() == "discriminant_as" {
+            if ident == "discriminant_as" {
                 match attribute.parse_args::<Path>() {
                     Ok(arg) => {
                         return quote! { #arg };
                     }
                     Err(_) => {
                         return syn::Error::new(attribute.span(), "invalid path")
-                            .to_compile_error()
-                            .into();
+                            .to_compile_error();
                     }
                 }
             }
@@ -269,3 +422,589 @@ fn get_discriminant(attrs: Vec<Attribute>) -> TokenStream2 {
 
     quote! { protocol::VarInt }
 }
+
+/// Statements writing each field and expressions for their sizes, given references to
+/// the field values
+fn write_fields(
+    fields: &Fields,
+    values: &[TokenStream2],
+) -> (Vec<TokenStream2>, Vec<TokenStream2>) {
+    let names: Vec<_> = fields.iter().filter_map(|f| f.ident.as_ref()).collect();
+
+    fields
+        .iter()
+        .zip(values)
+        .enumerate()
+        .map(|(i, (f, value))| {
+            // Named fields can refer to the ones before them in conditions
+            let scope = field_scope(&names[..i.min(names.len())], &values[..i]);
+
+            (
+                FieldEncoding::write(f, value, &scope),
+                FieldEncoding::size(f, value, &scope),
+            )
+        })
+        .unzip()
+}
+
+/// Binds the fields with their names to references to their values, for evaluating
+/// the conditions of `#[when(condition)]` fields
+fn field_scope(names: &[&syn::Ident], values: &[TokenStream2]) -> TokenStream2 {
+    quote! {
+        #(
+            #[allow(unused_variables)]
+            let #names = #values;
+        )*
+    }
+}
+
+/// The condition of a field that's only present when it holds, from `#[when(condition)]`,
+/// and the field without the `Option` around it
+fn get_condition(field: &syn::Field) -> Option<syn::Result<(Expr, syn::Field)>> {
+    let attribute = field.attrs.iter().find(|a| a.path.is_ident("when"))?;
+
+    let inner_type = match &field.ty {
+        syn::Type::Path(path) => path.path.segments.last().and_then(|segment| {
+            match (&segment.arguments, segment.ident == "Option") {
+                (syn::PathArguments::AngleBracketed(args), true) => match args.args.first() {
+                    Some(syn::GenericArgument::Type(inner)) => Some(inner.clone()),
+                    _ => None,
+                },
+                _ => None,
+            }
+        }),
+        _ => None,
+    };
+    let Some(inner_type) = inner_type else {
+        return Some(Err(syn::Error::new(
+            field.ty.span(),
+            "conditional fields must be `Option`s",
+        )));
+    };
+
+    let inner = syn::Field {
+        attrs: field
+            .attrs
+            .iter()
+            .filter(|a| !a.path.is_ident("when"))
+            .cloned()
+            .collect(),
+        ty: inner_type,
+        ..field.clone()
+    };
+
+    Some(
+        attribute
+            .parse_args::<Expr>()
+            .map(|condition| (condition, inner)),
+    )
+}
+
+/// How a field is encoded, from its attributes
+///
+/// See the `protocol::encoding` docs for the attributes.
+enum FieldEncoding {
+    /// By the field type's own impls
+    Type,
+    /// By a `protocol::encoding::Encoding` for the field type
+    With(TokenStream2),
+    /// Not encoded, set to the given value when reading
+    Skip(TokenStream2),
+}
+
+impl FieldEncoding {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let mut encoding = Self::Type;
+
+        for attribute in &field.attrs {
+            let Some(ident) = attribute.path.get_ident() else {
+                continue;
+            };
+
+            let parsed = match ident.to_string().as_str() {
+                "prefixed" => {
+                    let prefix = attribute.parse_args::<Path>()?;
+
+                    if prefix.is_ident("none") {
+                        Self::With(quote! { protocol::encoding::Rest })
+                    } else if prefix.is_ident("VarInt") {
+                        Self::With(quote! { protocol::encoding::Prefixed<protocol::VarInt> })
+                    } else {
+                        Self::With(quote! { protocol::encoding::Prefixed<#prefix> })
+                    }
+                }
+                "varint" => Self::With(quote! { protocol::encoding::AsVarInt }),
+                "varlong" => Self::With(quote! { protocol::encoding::AsVarLong }),
+                "angle" => Self::With(quote! { protocol::encoding::Angle }),
+                "fixed_point" => {
+                    let bits = attribute.parse_args::<Expr>()?;
+                    Self::With(quote! { protocol::encoding::FixedPoint<{ #bits }> })
+                }
+                "skip" if attribute.tokens.is_empty() => {
+                    Self::Skip(quote! { ::std::default::Default::default() })
+                }
+                "skip" => {
+                    let value = attribute.parse_args::<Expr>()?;
+                    Self::Skip(quote! { #value })
+                }
+                _ => continue,
+            };
+
+            if !matches!(encoding, Self::Type) {
+                return Err(syn::Error::new(
+                    attribute.span(),
+                    "a field can only have one encoding",
+                ));
+            }
+            encoding = parsed;
+        }
+
+        Ok(encoding)
+    }
+
+    /// Expression reading the field, as a `Result`
+    ///
+    /// `scope` binds the fields before it, for conditions.
+    fn read(field: &syn::Field, read_fn: &TokenStream2, scope: &TokenStream2) -> TokenStream2 {
+        let ty = &field.ty;
+
+        match get_condition(field) {
+            Some(Ok((condition, inner))) => {
+                let read = Self::read(&inner, read_fn, scope);
+
+                return quote! {
+                    (if { #scope #condition } { #read.map(Some) } else { Ok(None) })
+                };
+            }
+            Some(Err(e)) => return e.to_compile_error(),
+            None => {}
+        }
+
+        match Self::parse(field) {
+            Ok(Self::Type) => quote! { #read_fn(read) },
+            Ok(Self::With(encoding)) => quote! {
+                <#encoding as protocol::encoding::Encoding<#ty>>::read(read)
+            },
+            Ok(Self::Skip(value)) => quote! { ::std::io::Result::<#ty>::Ok(#value) },
+            Err(e) => e.to_compile_error(),
+        }
+    }
+
+    /// Statement adding the bytes written for `value`, a reference to the field, to `written`
+    fn write(field: &syn::Field, value: &TokenStream2, scope: &TokenStream2) -> TokenStream2 {
+        let ty = &field.ty;
+
+        match get_condition(field) {
+            Some(Ok((condition, inner))) => {
+                let write = Self::write(&inner, &quote! { __inner }, scope);
+                let missing = match &field.ident {
+                    Some(name) => format!("`{name}` is missing, but its condition holds"),
+                    None => "Field is missing, but its condition holds".to_string(),
+                };
+
+                return quote! {
+                    if { #scope #condition } {
+                        match #value {
+                            Some(__inner) => { #write }
+                            None => return Err(::std::io::Error::new(
+                                ::std::io::ErrorKind::InvalidInput,
+                                #missing,
+                            )),
+                        }
+                    }
+                };
+            }
+            Some(Err(e)) => return e.to_compile_error(),
+            None => {}
+        }
+
+        match Self::parse(field) {
+            Ok(Self::Type) => quote! { written += ToBytes::write_to(#value, write)?; },
+            Ok(Self::With(encoding)) => quote! {
+                written += <#encoding as protocol::encoding::Encoding<#ty>>::write(#value, write)?;
+            },
+            Ok(Self::Skip(_)) => quote! {},
+            Err(e) => e.to_compile_error(),
+        }
+    }
+
+    /// Expression for the encoded size of `value`, a reference to the field
+    fn size(field: &syn::Field, value: &TokenStream2, scope: &TokenStream2) -> TokenStream2 {
+        let ty = &field.ty;
+
+        match get_condition(field) {
+            Some(Ok((condition, inner))) => {
+                let size = Self::size(&inner, &quote! { __inner }, scope);
+
+                return quote! {
+                    (if { #scope #condition } {
+                        match #value {
+                            Some(__inner) => #size,
+                            None => 0,
+                        }
+                    } else {
+                        0
+                    })
+                };
+            }
+            Some(Err(e)) => return e.to_compile_error(),
+            None => {}
+        }
+
+        match Self::parse(field) {
+            Ok(Self::Type) => quote! { ToBytes::encoded_size(#value) },
+            Ok(Self::With(encoding)) => quote! {
+                <#encoding as protocol::encoding::Encoding<#ty>>::size(#value)
+            },
+            Ok(Self::Skip(_)) => quote! { 0 },
+            Err(e) => e.to_compile_error(),
+        }
+    }
+}
+
+/// The element limit of a field, from `#[max_len(N)]`
+fn get_max_len(attrs: &[Attribute]) -> Option<TokenStream2> {
+    let attribute = attrs.iter().find(|a| a.path.is_ident("max_len"))?;
+
+    Some(match attribute.parse_args::<Expr>() {
+        Ok(max_len) => quote! { #max_len },
+        Err(_) => syn::Error::new(attribute.span(), "expected a length").to_compile_error(),
+    })
+}
+
+/// An enum variant with its packet ids and field layouts per protocol version
+struct VersionedVariant {
+    name: syn::Ident,
+    /// `None` for unit variants
+    field_type: Option<syn::Type>,
+    /// Packet ids with the version range they are used in
+    ids: Vec<(i32, TokenStream2)>,
+    /// Alternative field layouts with the version range they are used in
+    layouts: Vec<(Path, TokenStream2)>,
+}
+
+/// Parses the `#[id(..)]` and `#[layout(..)]` attributes of an enum's variants
+///
+/// Variants without an `id` attribute use the same id in all versions, which is
+/// determined the same way as in the `FromBytes` derive.
+fn versioned_variants(data: Data) -> syn::Result<Vec<VersionedVariant>> {
+    let Data::Enum(data) = data else {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "Versioned packets must be enums",
+        ));
+    };
+
+    let mut variants = Vec::new();
+    let mut next_discriminant = 0;
+
+    for variant in data.variants {
+        if let Some((
+            _,
+            Expr::Lit(ExprLit {
+                lit: Lit::Int(d), ..
+            }),
+        )) = &variant.discriminant
+        {
+            next_discriminant = d.base10_parse()?;
+        }
+
+        let field_type = match &variant.fields {
+            Fields::Unit => None,
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                Some(fields.unnamed[0].ty.clone())
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    variant.span(),
+                    "Versioned packets must be unit or single field tuple variants",
+                ))
+            }
+        };
+
+        let mut ids = Vec::new();
+        let mut layouts = Vec::new();
+
+        for attribute in &variant.attrs {
+            let is_id = attribute.path.is_ident("id");
+            if !is_id && !attribute.path.is_ident("layout") {
+                continue;
+            }
+
+            let syn::Meta::List(list) = attribute.parse_meta()? else {
+                return Err(syn::Error::new(attribute.span(), "expected a list"));
+            };
+
+            let mut id = None;
+            let mut layout = None;
+            let mut since = quote! { i32::MIN };
+            let mut until = quote! { i32::MAX };
+
+            for nested in list.nested {
+                match nested {
+                    syn::NestedMeta::Lit(Lit::Int(lit)) if is_id => {
+                        id = Some(lit.base10_parse::<i32>()?);
+                    }
+                    syn::NestedMeta::Meta(syn::Meta::Path(path)) if !is_id => {
+                        layout = Some(path);
+                    }
+                    syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                        path,
+                        lit: Lit::Int(lit),
+                        ..
+                    })) if path.is_ident("since") || path.is_ident("until") => {
+                        let version = lit.base10_parse::<i32>()?;
+
+                        if path.is_ident("since") {
+                            since = quote! { #version };
+                        } else {
+                            until = quote! { #version };
+                        }
+                    }
+                    other => return Err(syn::Error::new(other.span(), "unexpected argument")),
+                }
+            }
+
+            let range = quote! { (#since..=#until) };
+
+            match (id, layout) {
+                (Some(id), _) => ids.push((id, range)),
+                (_, Some(layout)) if field_type.is_some() => layouts.push((layout, range)),
+                _ => return Err(syn::Error::new(attribute.span(), "missing id or layout")),
+            }
+        }
+
+        if ids.is_empty() {
+            ids.push((next_discriminant, quote! { (i32::MIN..=i32::MAX) }));
+        }
+
+        variants.push(VersionedVariant {
+            name: variant.ident,
+            field_type,
+            ids,
+            layouts,
+        });
+
+        next_discriminant += 1;
+    }
+
+    Ok(variants)
+}
+
+#[proc_macro_derive(FromBytesVersioned, attributes(discriminant_as, id, layout))]
+pub fn derive_from_bytes_versioned(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let name = input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let discriminant_type = get_discriminant(input.attrs);
+
+    let variants = match versioned_variants(input.data) {
+        Ok(variants) => variants,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    let type_name = name.to_string();
+    let mut checks = Vec::new();
+
+    for variant in &variants {
+        let variant_name = &variant.name;
+        let variant_str = variant_name.to_string();
+        let in_variant = quote! {
+            map_err(|e| protocol::DecodeError::in_variant(e, #type_name, #variant_str))
+        };
+
+        let construct = match &variant.field_type {
+            Some(field_type) => {
+                let layouts = variant.layouts.iter().map(|(layout, range)| {
+                    quote! {
+                        if #range.contains(&version) {
+                            let packet: #layout = protocol::FromBytes::read_from(read).#in_variant?;
+                            return Ok(Self::#variant_name(::std::convert::From::from(packet)));
+                        }
+                    }
+                });
+
+                quote! {
+                    #(#layouts)*
+                    let packet: #field_type = protocol::FromBytes::read_from(read).#in_variant?;
+                    return Ok(Self::#variant_name(packet));
+                }
+            }
+            None => quote! {
+                return Ok(Self::#variant_name);
+            },
+        };
+
+        for (id, range) in &variant.ids {
+            checks.push(quote! {
+                if id == #id && #range.contains(&version) {
+                    #construct
+                }
+            });
+        }
+    }
+
+    let expanded = quote! {
+        impl #impl_generics protocol::FromBytesVersioned for #name #ty_generics #where_clause {
+            fn read_versioned<__R: ::std::io::Read>(
+                read: &mut __R,
+                version: protocol::ProtocolVersion,
+            ) -> ::std::io::Result<Self> {
+                let version = version.0;
+                let id: #discriminant_type = protocol::FromBytes::read_from(read)?;
+                let id: i32 = ::std::convert::Into::into(id);
+
+                #(#checks)*
+
+                let error = ::std::io::Error::new(
+                    ::std::io::ErrorKind::InvalidData,
+                    format!("Invalid packet id {id} for protocol version {version}"),
+                );
+
+                Err(protocol::DecodeError::in_type(error, #type_name))
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+#[proc_macro_derive(ToBytesVersioned, attributes(discriminant_as, id, layout))]
+pub fn derive_to_bytes_versioned(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let name = input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let discriminant_type = get_discriminant(input.attrs);
+
+    let variants = match versioned_variants(input.data) {
+        Ok(variants) => variants,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    let mut write_arms = Vec::new();
+    let mut size_arms = Vec::new();
+    let mut available_arms = Vec::new();
+
+    for variant in &variants {
+        let variant_name = &variant.name;
+        let id_ranges = variant.ids.iter().map(|(_, range)| range);
+        available_arms.push(quote! {
+            Self::#variant_name { .. } => false #(|| #id_ranges.contains(&version))*,
+        });
+        let ids = variant.ids.iter().map(|(id, range)| {
+            (
+                range,
+                quote! { ::std::convert::Into::< #discriminant_type >::into(#id) },
+            )
+        });
+
+        match &variant.field_type {
+            Some(field_type) => {
+                let (ranges, ids): (Vec<_>, Vec<_>) = ids.unzip();
+
+                let write_layouts: TokenStream2 = variant.layouts.iter().map(|(layout, range)| {
+                    quote! {
+                        if #range.contains(&version) {
+                            let packet = <#layout as ::std::convert::From<&#field_type>>::from(packet);
+                            return Ok(id_written + protocol::ToBytes::write_to(&packet, write)?);
+                        }
+                    }
+                }).collect();
+                let size_layouts: TokenStream2 = variant.layouts.iter().map(|(layout, range)| {
+                    quote! {
+                        if #range.contains(&version) {
+                            let packet = <#layout as ::std::convert::From<&#field_type>>::from(packet);
+                            return id_size + protocol::ToBytes::encoded_size(&packet);
+                        }
+                    }
+                }).collect();
+
+                write_arms.push(quote! {
+                    Self::#variant_name(packet) => {
+                        #(
+                            if #ranges.contains(&version) {
+                                let id_written = protocol::ToBytes::write_to(&#ids, write)?;
+                                #write_layouts
+                                return Ok(id_written + protocol::ToBytes::write_to(packet, write)?);
+                            }
+                        )*
+                    }
+                });
+                size_arms.push(quote! {
+                    Self::#variant_name(packet) => {
+                        #(
+                            if #ranges.contains(&version) {
+                                let id_size = protocol::ToBytes::encoded_size(&#ids);
+                                #size_layouts
+                                return id_size + protocol::ToBytes::encoded_size(packet);
+                            }
+                        )*
+                    }
+                });
+            }
+            None => {
+                let (ranges, ids): (Vec<_>, Vec<_>) = ids.unzip();
+
+                write_arms.push(quote! {
+                    Self::#variant_name => {
+                        #(
+                            if #ranges.contains(&version) {
+                                return protocol::ToBytes::write_to(&#ids, write);
+                            }
+                        )*
+                    }
+                });
+                size_arms.push(quote! {
+                    Self::#variant_name => {
+                        #(
+                            if #ranges.contains(&version) {
+                                return protocol::ToBytes::encoded_size(&#ids);
+                            }
+                        )*
+                    }
+                });
+            }
+        }
+    }
+
+    let expanded = quote! {
+        impl #impl_generics protocol::ToBytesVersioned for #name #ty_generics #where_clause {
+            fn write_versioned<__W: ::std::io::Write>(
+                &self,
+                write: &mut __W,
+                version: protocol::ProtocolVersion,
+            ) -> ::std::io::Result<usize> {
+                let version = version.0;
+
+                match self {
+                    #(#write_arms)*
+                }
+
+                Err(::std::io::Error::new(
+                    ::std::io::ErrorKind::InvalidInput,
+                    "Packet not available in the protocol version",
+                ))
+            }
+            fn versioned_size(&self, version: protocol::ProtocolVersion) -> usize {
+                let version = version.0;
+
+                match self {
+                    #(#size_arms)*
+                }
+
+                0
+            }
+            fn is_available(&self, version: protocol::ProtocolVersion) -> bool {
+                let version = version.0;
+
+                match self {
+                    #(#available_arms)*
+                }
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
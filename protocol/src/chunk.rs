@@ -0,0 +1,556 @@
+//! The chunk section format of [`ChunkData`](crate::packets::play::ChunkData)
+//!
+//! A chunk column is sent as its sections from the bottom of the world up, each with the
+//! number of non-air blocks, the block states and the biomes. Both are paletted
+//! containers, which are written with the smallest palette that fits the distinct values:
+//!
+//! - single-valued, with 0 bits per entry and no data
+//! - indirect, with a list of the values and indices into it packed into longs
+//! - direct, with the values themselves packed into longs
+//!
+//! Entries never span two longs, so some bits at the end of each long may be unused.
+
+use crate::{FromBytes, ToBytes, VarInt};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    io::{self, Read, Write},
+    marker::PhantomData,
+};
+
+/// What a [`PalettedContainer`] holds
+pub trait ContainerKind {
+    /// Entries along each axis
+    const EDGE: usize;
+    /// Indirect palettes use at least this many bits per entry
+    const MIN_INDIRECT_BITS: u8;
+    /// Containers that need more bits use the direct palette
+    const MAX_INDIRECT_BITS: u8;
+    /// Bits per entry of the direct palette with the vanilla registry
+    const DIRECT_BITS: u8;
+}
+
+/// Block state ids, one per block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockStates {}
+
+/// Biome ids, one per 4×4×4 blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Biomes {}
+
+impl ContainerKind for BlockStates {
+    const EDGE: usize = 16;
+    const MIN_INDIRECT_BITS: u8 = 4;
+    const MAX_INDIRECT_BITS: u8 = 8;
+    const DIRECT_BITS: u8 = 15;
+}
+
+impl ContainerKind for Biomes {
+    const EDGE: usize = 4;
+    const MIN_INDIRECT_BITS: u8 = 1;
+    const MAX_INDIRECT_BITS: u8 = 3;
+    const DIRECT_BITS: u8 = 6;
+}
+
+/// The values of a chunk section, indexed by `(y * EDGE + z) * EDGE + x`
+#[derive(Clone, PartialEq, Eq)]
+pub struct PalettedContainer<K> {
+    values: Box<[u32]>,
+    direct_bits: u8,
+    kind: PhantomData<K>,
+}
+
+pub type BlockStateContainer = PalettedContainer<BlockStates>;
+pub type BiomeContainer = PalettedContainer<Biomes>;
+
+/// How a container is written
+enum Palette {
+    Single(u32),
+    Indirect { bits: u8, values: Vec<u32> },
+    Direct { bits: u8 },
+}
+
+impl<K: ContainerKind> PalettedContainer<K> {
+    pub const ENTRIES: usize = K::EDGE * K::EDGE * K::EDGE;
+
+    /// All entries set to `value`
+    pub fn new(value: u32) -> Self {
+        Self::from_values(vec![value; Self::ENTRIES].into_boxed_slice())
+    }
+    /// Panics if there aren't exactly [`Self::ENTRIES`] values
+    pub fn from_values(values: impl Into<Box<[u32]>>) -> Self {
+        let values = values.into();
+        assert_eq!(values.len(), Self::ENTRIES, "Wrong number of values");
+
+        Self {
+            values,
+            direct_bits: K::DIRECT_BITS,
+            kind: PhantomData,
+        }
+    }
+    /// Sets the bits per entry of the direct palette, which is the number of bits needed
+    /// for the ids of the registry
+    ///
+    /// It's only needed for registries other than vanilla's.
+    ///
+    /// # Panics
+    ///
+    /// If `bits` is not more than `K::MAX_INDIRECT_BITS`, as the client would read it as
+    /// an indirect palette, or if it's more than 32.
+    pub fn with_direct_bits(mut self, bits: u8) -> Self {
+        assert!(
+            bits > K::MAX_INDIRECT_BITS && bits <= 32,
+            "Invalid bits per entry for the direct palette"
+        );
+        self.direct_bits = bits;
+        self
+    }
+    pub fn values(&self) -> &[u32] {
+        &self.values
+    }
+    /// Panics if a coordinate is not less than `EDGE`
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u32 {
+        self.values[Self::index(x, y, z)]
+    }
+    /// Panics if a coordinate is not less than `EDGE`
+    pub fn set(&mut self, x: usize, y: usize, z: usize, value: u32) {
+        self.values[Self::index(x, y, z)] = value;
+    }
+    pub fn fill(&mut self, value: u32) {
+        self.values.fill(value);
+    }
+
+    fn index(x: usize, y: usize, z: usize) -> usize {
+        assert!(
+            x < K::EDGE && y < K::EDGE && z < K::EDGE,
+            "Coordinates out of bounds"
+        );
+
+        (y * K::EDGE + z) * K::EDGE + x
+    }
+
+    fn palette(&self) -> Palette {
+        // In order of first use, like vanilla
+        let mut seen = HashSet::new();
+        let distinct: Vec<u32> = self
+            .values
+            .iter()
+            .copied()
+            .filter(|v| seen.insert(*v))
+            .collect();
+
+        if distinct.len() == 1 {
+            return Palette::Single(distinct[0]);
+        }
+
+        let bits = (usize::BITS - (distinct.len() - 1).leading_zeros()) as u8;
+        match bits.max(K::MIN_INDIRECT_BITS) {
+            bits if bits <= K::MAX_INDIRECT_BITS => Palette::Indirect {
+                bits,
+                values: distinct,
+            },
+            _ => Palette::Direct {
+                bits: self.direct_bits,
+            },
+        }
+    }
+}
+
+/// Number of longs for `entries` entries of `bits` bits each
+fn packed_length(entries: usize, bits: u8) -> usize {
+    let per_long = 64 / bits as usize;
+
+    entries.div_ceil(per_long)
+}
+
+fn pack(indices: impl Iterator<Item = u32>, bits: u8) -> Vec<u64> {
+    let per_long = 64 / bits as usize;
+    let mut longs = Vec::new();
+
+    for (i, index) in indices.enumerate() {
+        if i % per_long == 0 {
+            longs.push(0);
+        }
+        *longs.last_mut().unwrap() |= (index as u64) << (i % per_long * bits as usize);
+    }
+
+    longs
+}
+
+fn unpack(longs: &[u64], bits: u8, entries: usize) -> impl Iterator<Item = u32> + '_ {
+    let per_long = 64 / bits as usize;
+    let mask = (1u64 << bits) - 1;
+
+    (0..entries).map(move |i| (longs[i / per_long] >> (i % per_long * bits as usize) & mask) as u32)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl<K: ContainerKind> FromBytes for PalettedContainer<K> {
+    fn read_from<R: Read>(read: &mut R) -> io::Result<Self> {
+        let bits = u8::read_from(read)?;
+
+        let palette = match bits {
+            0 => Palette::Single(VarInt::read_from(read)?.0 as u32),
+            // The client uses the smallest indirect palette for fewer bits too
+            _ if bits <= K::MAX_INDIRECT_BITS => Palette::Indirect {
+                bits: bits.max(K::MIN_INDIRECT_BITS),
+                values: Vec::<VarInt>::read_from(read)?
+                    .into_iter()
+                    .map(|v| v.0 as u32)
+                    .collect(),
+            },
+            _ if bits <= 32 => Palette::Direct { bits },
+            _ => return Err(invalid_data("Too many bits per entry")),
+        };
+
+        let longs = Vec::<u64>::read_from(read)?;
+        let bits = match palette {
+            Palette::Single(_) => 0,
+            Palette::Indirect { bits, .. } | Palette::Direct { bits } => bits,
+        };
+        let expected_length = match bits {
+            0 => 0,
+            _ => packed_length(Self::ENTRIES, bits),
+        };
+        if longs.len() != expected_length {
+            return Err(invalid_data("Wrong length of paletted container data"));
+        }
+
+        let container = match palette {
+            Palette::Single(value) => Self::new(value),
+            Palette::Indirect { bits, values } => {
+                let values = unpack(&longs, bits, Self::ENTRIES)
+                    .map(|i| values.get(i as usize).copied())
+                    .collect::<Option<Box<[u32]>>>()
+                    .ok_or_else(|| invalid_data("Palette index out of bounds"))?;
+
+                Self::from_values(values)
+            }
+            Palette::Direct { bits } => {
+                Self::from_values(unpack(&longs, bits, Self::ENTRIES).collect::<Box<[u32]>>())
+                    .with_direct_bits(bits)
+            }
+        };
+
+        Ok(container)
+    }
+}
+
+impl<K: ContainerKind> ToBytes for PalettedContainer<K> {
+    fn write_to<W: Write>(&self, write: &mut W) -> io::Result<usize> {
+        let (bits, palette, longs) = match self.palette() {
+            Palette::Single(value) => (0, vec![VarInt(value as i32)], Vec::new()),
+            Palette::Indirect { bits, values } => {
+                let indices: HashMap<_, _> = values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| (*v, i as u32))
+                    .collect();
+                let longs = pack(self.values.iter().map(|v| indices[v]), bits);
+
+                (
+                    bits,
+                    values.iter().map(|v| VarInt(*v as i32)).collect(),
+                    longs,
+                )
+            }
+            Palette::Direct { bits } => {
+                if self.values.iter().any(|v| (*v as u64) >> bits != 0) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "Value too large for the direct palette",
+                    ));
+                }
+
+                (bits, Vec::new(), pack(self.values.iter().copied(), bits))
+            }
+        };
+
+        let mut written = bits.write_to(write)?;
+        written += match bits {
+            0 => palette[0].write_to(write)?,
+            _ if bits <= K::MAX_INDIRECT_BITS => palette.write_to(write)?,
+            _ => 0,
+        };
+        written += longs.write_to(write)?;
+
+        Ok(written)
+    }
+    fn encoded_size(&self) -> usize {
+        match self.palette() {
+            Palette::Single(value) => 1 + VarInt(value as i32).encoded_size() + 1,
+            Palette::Indirect { bits, values } => {
+                let palette: usize = values
+                    .iter()
+                    .map(|v| VarInt(*v as i32).encoded_size())
+                    .sum();
+                let longs = packed_length(Self::ENTRIES, bits);
+
+                1 + VarInt(values.len() as i32).encoded_size()
+                    + palette
+                    + VarInt(longs as i32).encoded_size()
+                    + longs * 8
+            }
+            Palette::Direct { bits } => {
+                let longs = packed_length(Self::ENTRIES, bits);
+
+                1 + VarInt(longs as i32).encoded_size() + longs * 8
+            }
+        }
+    }
+}
+
+impl<K> fmt::Debug for PalettedContainer<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut distinct = self.values.to_vec();
+        distinct.sort_unstable();
+        distinct.dedup();
+
+        f.debug_struct("PalettedContainer")
+            .field("distinct_values", &distinct)
+            .finish()
+    }
+}
+
+/// A 16×16×16 section of a chunk column
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq, Eq)]
+pub struct ChunkSection {
+    /// Number of blocks that aren't air, see [`ChunkSection::count_blocks`]
+    pub block_count: i16,
+    pub block_states: BlockStateContainer,
+    pub biomes: BiomeContainer,
+}
+
+impl ChunkSection {
+    /// Filled with air, which is block state 0
+    pub fn empty(biome: u32) -> Self {
+        Self {
+            block_count: 0,
+            block_states: BlockStateContainer::new(0),
+            biomes: BiomeContainer::new(biome),
+        }
+    }
+    /// Sets `block_count` from the block states, as vanilla has 3 kinds of air
+    pub fn count_blocks(&mut self, is_air: impl Fn(u32) -> bool) {
+        let count = self
+            .block_states
+            .values()
+            .iter()
+            .filter(|v| !is_air(**v))
+            .count();
+
+        self.block_count = count as i16;
+    }
+}
+
+/// The light levels of a chunk section, half a byte per block
+///
+/// Indexed like [`PalettedContainer`], with even indices in the lower half of a byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LightArray(pub Box<[u8; 2048]>);
+
+impl LightArray {
+    /// All blocks at `level`, which is 0 to 15
+    pub fn filled(level: u8) -> Self {
+        Self(Box::new([level & 0xF | level << 4; 2048]))
+    }
+    /// Panics if a coordinate is not less than 16
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u8 {
+        let index = BlockStateContainer::index(x, y, z);
+
+        self.0[index / 2] >> (index % 2 * 4) & 0xF
+    }
+    /// Panics if a coordinate is not less than 16
+    pub fn set(&mut self, x: usize, y: usize, z: usize, level: u8) {
+        let index = BlockStateContainer::index(x, y, z);
+        let shift = index % 2 * 4;
+
+        let byte = &mut self.0[index / 2];
+        *byte = *byte & !(0xF << shift) | (level & 0xF) << shift;
+    }
+}
+
+impl Default for LightArray {
+    fn default() -> Self {
+        Self::filled(0)
+    }
+}
+
+impl FromBytes for LightArray {
+    fn read_from<R: Read>(read: &mut R) -> io::Result<Self> {
+        if VarInt::read_from(read)?.0 != 2048 {
+            return Err(invalid_data("Light arrays must be 2048 bytes"));
+        }
+
+        let mut array = Self::default();
+        read.read_exact(&mut array.0[..])?;
+
+        Ok(array)
+    }
+}
+
+impl ToBytes for LightArray {
+    fn write_to<W: Write>(&self, write: &mut W) -> io::Result<usize> {
+        let written = VarInt(2048).write_to(write)?;
+        write.write_all(&self.0[..])?;
+
+        Ok(written + 2048)
+    }
+    fn encoded_size(&self) -> usize {
+        2 + 2048
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BiomeContainer, BlockStateContainer, ChunkSection, LightArray};
+    use crate::{FromBytes, ToBytes};
+
+    fn round_trip(section: &ChunkSection, expected: &[u8]) {
+        let mut bytes = Vec::new();
+        section.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes, expected);
+        assert_eq!(section.encoded_size(), expected.len());
+        assert_eq!(&ChunkSection::read_from(&mut &bytes[..]).unwrap(), section);
+    }
+
+    #[test]
+    fn chunk_section_read_and_write() {
+        // An empty section in plains: no blocks, single-valued air and plains
+        let mut section = ChunkSection::empty(39);
+        round_trip(&section, &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x27, 0x00]);
+
+        // Stone at the bottom layer: an indirect palette of stone and air with 4 bits
+        // per entry, which fills 16 longs for the bottom layer and 240 for the rest
+        for x in 0..16 {
+            for z in 0..16 {
+                section.block_states.set(x, 0, z, 1);
+            }
+        }
+        // And a second biome in the top half: 1 bit per entry in 1 long
+        for x in 0..4 {
+            for y in 2..4 {
+                for z in 0..4 {
+                    section.biomes.set(x, y, z, 1);
+                }
+            }
+        }
+        section.count_blocks(|id| id == 0);
+
+        let mut expected = vec![0x01, 0x00, 0x04, 0x02, 0x01, 0x00, 0x80, 0x02];
+        expected.extend([0x00; 16 * 8]);
+        expected.extend([0x11; 240 * 8]);
+        expected.extend([0x01, 0x02, 0x27, 0x01, 0x01]);
+        expected.extend(0xFFFFFFFF00000000u64.to_be_bytes());
+        round_trip(&section, &expected);
+
+        // 300 distinct blocks need the direct palette, with 15 bits and 4 entries per long
+        for i in 0..300 {
+            section
+                .block_states
+                .set(i % 16, 15 - i / 256, i / 16 % 16, i as u32 + 1);
+        }
+        let mut bytes = Vec::new();
+        section.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes[2..5], [0x0F, 0x80, 0x08]);
+        assert_eq!(bytes.len(), section.encoded_size());
+        assert_eq!(ChunkSection::read_from(&mut &bytes[..]).unwrap(), section);
+
+        // The client reads fewer bits than the minimum as the minimum
+        let mut bytes = vec![0x02, 0x02, 0x05, 0x09, 0x80, 0x02];
+        bytes.extend([0x10; 256 * 8]);
+        let container = BlockStateContainer::read_from(&mut &bytes[..]).unwrap();
+        assert!(container
+            .values()
+            .iter()
+            .enumerate()
+            .all(|(i, v)| *v == [5, 9][i % 2]));
+
+        // Palette indices must be in the palette
+        assert!(BiomeContainer::read_from(
+            &mut &[0x01, 0x01, 0x00, 0x01, 0, 0, 0, 0, 0, 0, 0, 2][..]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn vanilla_sections() {
+        // The bottom section of a default superflat chunk as vanilla 1.20.2 writes it:
+        // bedrock, two layers of dirt and grass. Vanilla's palette is in the order the
+        // blocks were placed, starting with the air it was filled with.
+        let mut bytes = vec![0x04, 0x00, 0x04, 0x04, 0x00, 0x4F, 0x0A, 0x09, 0x80, 0x02];
+        for long in [0x11, 0x22, 0x22, 0x33] {
+            bytes.extend([long; 16 * 8]);
+        }
+        bytes.extend([0x00; 192 * 8]);
+        bytes.extend([0x00, 0x27, 0x00]);
+
+        let section = ChunkSection::read_from(&mut &bytes[..]).unwrap();
+        assert_eq!(section.block_count, 1024);
+        for (y, id) in [(0, 79), (1, 10), (2, 10), (3, 9), (4, 0), (15, 0)] {
+            assert_eq!(section.block_states.get(5, y, 11), id);
+        }
+        assert_eq!(section.biomes.values(), &[39; 64]);
+
+        let mut written = Vec::new();
+        section.write_to(&mut written).unwrap();
+        assert_eq!(written.len(), bytes.len());
+        assert_eq!(ChunkSection::read_from(&mut &written[..]).unwrap(), section);
+
+        // Vanilla's direct palette has no entries, and the 15 bit ids leave the top 4 bits
+        // of each long unused
+        let mut bytes = vec![0x0F, 0x80, 0x08];
+        for i in (0..4096u64).step_by(4) {
+            let long = i | (i + 1) << 15 | (i + 2) << 30 | (i + 3) << 45;
+            bytes.extend(long.to_be_bytes());
+        }
+        let container = BlockStateContainer::read_from(&mut &bytes[..]).unwrap();
+        assert!(container
+            .values()
+            .iter()
+            .enumerate()
+            .all(|(i, v)| *v == i as u32));
+
+        let mut written = Vec::new();
+        container.write_to(&mut written).unwrap();
+        assert_eq!(written, bytes);
+    }
+
+    #[test]
+    fn direct_bits() {
+        // Registries with more biomes need more bits
+        let mut biomes = BiomeContainer::new(0).with_direct_bits(7);
+        for i in 0..16 {
+            biomes.set(i % 4, 0, i / 4, i as u32 * 8);
+        }
+        let mut bytes = Vec::new();
+        biomes.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes[0], 7);
+        assert_eq!(BiomeContainer::read_from(&mut &bytes[..]).unwrap(), biomes);
+
+        for bits in [3, 33] {
+            let result = std::panic::catch_unwind(|| BiomeContainer::new(0).with_direct_bits(bits));
+            assert!(result.is_err(), "{bits} bits accepted");
+        }
+    }
+
+    #[test]
+    fn light_array() {
+        let mut light = LightArray::filled(15);
+        light.set(1, 0, 0, 3);
+        light.set(0, 0, 1, 7);
+        assert_eq!((light.get(0, 0, 0), light.get(1, 0, 0)), (15, 3));
+        assert_eq!(
+            light.0[..9],
+            [0x3F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xF7]
+        );
+
+        let mut bytes = Vec::new();
+        light.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes[..2], [0x80, 0x10]);
+        assert_eq!(LightArray::read_from(&mut &bytes[..]).unwrap(), light);
+    }
+}
@@ -0,0 +1,487 @@
+//! Async framing of packets, for use with `tokio_util::codec::Framed` and friends.
+//!
+//! Handles the VarInt length prefix, compression and encryption, so that only
+//! whole packets have to be dealt with.
+
+use crate::{
+    limits::{self, DecodeLimits},
+    DecodeError, FromBytes, ToBytes, VarInt,
+};
+use aes::{
+    cipher::{generic_array::GenericArray, BlockDecryptMut, BlockEncryptMut, KeyIvInit},
+    Aes128,
+};
+use bytes::{Buf, BufMut, BytesMut};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::{
+    io::{self, Read},
+    marker::PhantomData,
+};
+use tokio_util::codec::{Decoder, Encoder};
+
+type Encryptor = cfb8::Encryptor<Aes128>;
+type Decryptor = cfb8::Decryptor<Aes128>;
+
+/// Default maximum length of a single frame (VarInt length prefix not included)
+pub const MAX_FRAME_LENGTH: usize = 2097151;
+
+/// Default maximum declared uncompressed length of a compressed packet,
+/// to prevent decompression bombs
+pub const MAX_UNCOMPRESSED_LENGTH: usize = 8388608;
+
+/// Splits a stream into frames, yielding the (decompressed) packet data of each frame
+///
+/// Any type implementing [`ToBytes`] can be encoded.
+pub struct FrameCodec {
+    max_frame_length: usize,
+    max_uncompressed_length: usize,
+    /// Compression threshold, if compression is enabled
+    compression: Option<usize>,
+    cipher: Option<(Encryptor, Decryptor)>,
+    /// Length of the start of the read buffer that is already decrypted
+    decrypted: usize,
+    /// Compression buffer
+    buf: Vec<u8>,
+}
+
+impl FrameCodec {
+    pub fn new() -> Self {
+        Self {
+            max_frame_length: MAX_FRAME_LENGTH,
+            max_uncompressed_length: MAX_UNCOMPRESSED_LENGTH,
+            compression: None,
+            cipher: None,
+            decrypted: 0,
+            buf: Vec::new(),
+        }
+    }
+    pub fn max_frame_length(mut self, max: usize) -> Self {
+        self.max_frame_length = max;
+        self
+    }
+    pub fn max_uncompressed_length(mut self, max: usize) -> Self {
+        self.max_uncompressed_length = max;
+        self
+    }
+    /// Enables compression of packets at least `threshold` bytes long.
+    ///
+    /// Frames read after this are expected to be in the compressed format too.
+    pub fn enable_compression(&mut self, threshold: usize) {
+        self.compression = Some(threshold);
+    }
+    pub fn compression(&self) -> Option<usize> {
+        self.compression
+    }
+    /// Enables AES/CFB8 encryption with the given shared secret, which is used as
+    /// both the key and the IV.
+    ///
+    /// Data already buffered but not yet decoded is decrypted too.
+    pub fn enable_encryption(&mut self, shared_secret: &[u8; 16]) {
+        let key = GenericArray::from_slice(shared_secret);
+
+        self.cipher = Some((Encryptor::new(key, key), Decryptor::new(key, key)));
+        self.decrypted = 0;
+    }
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Replaces the contents of `frame` (without the packet length) with the uncompressed packet
+    fn decompress(&self, mut frame: BytesMut, threshold: usize) -> io::Result<BytesMut> {
+        let mut slice = &frame[..];
+        let data_length = VarInt::read_from(&mut slice)?.0;
+        let header_length = frame.len() - slice.len();
+
+        if data_length == 0 {
+            // Not compressed
+            frame.advance(header_length);
+            return Ok(frame);
+        }
+
+        if data_length < 0 || (data_length as usize) < threshold {
+            return Err(invalid("Compressed packet below compression threshold"));
+        }
+        if data_length as usize > self.max_uncompressed_length {
+            return Err(invalid("Declared uncompressed packet length too big"));
+        }
+
+        let mut decompressed = Vec::with_capacity(data_length as usize);
+        // Never decompress more than declared
+        ZlibDecoder::new(slice)
+            .take(data_length as u64 + 1)
+            .read_to_end(&mut decompressed)?;
+
+        if decompressed.len() != data_length as usize {
+            return Err(invalid(
+                "Uncompressed packet length doesn't match the declared length",
+            ));
+        }
+
+        Ok(BytesMut::from(&decompressed[..]))
+    }
+    /// Appends the unencrypted frame of `item` to `dst`
+    fn write_frame<T: ToBytes>(&mut self, item: &T, dst: &mut BytesMut) -> io::Result<()> {
+        let length = item.encoded_size();
+
+        match self.compression {
+            None => {
+                check_length(length, self.max_frame_length)?;
+                dst.reserve(5 + length);
+                put_varint(dst, length);
+                write_exact(item, dst, length)?;
+            }
+            Some(threshold) if length < threshold => {
+                check_length(length + 1, self.max_frame_length)?;
+                dst.reserve(6 + length);
+                put_varint(dst, length + 1);
+                // Data length of 0 means the packet is not compressed
+                put_varint(dst, 0);
+                write_exact(item, dst, length)?;
+            }
+            Some(_) => {
+                check_length(length, self.max_uncompressed_length)?;
+
+                // The frame length depends on the compressed length,
+                // so it has to be compressed separately first
+                self.buf.clear();
+                let mut encoder = ZlibEncoder::new(&mut self.buf, Compression::default());
+                let written = item.write_to(&mut encoder)?;
+                encoder.finish()?;
+
+                if written != length {
+                    return Err(size_mismatch());
+                }
+
+                let packet_length = VarInt(length as i32).encoded_size() + self.buf.len();
+                check_length(packet_length, self.max_frame_length)?;
+                dst.reserve(5 + packet_length);
+                put_varint(dst, packet_length);
+                put_varint(dst, length);
+                dst.extend_from_slice(&self.buf);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for FrameCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for FrameCodec {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+        if let Some((_, decryptor)) = &mut self.cipher {
+            // CFB8 works on single byte blocks
+            for byte in src[self.decrypted..].chunks_exact_mut(1) {
+                decryptor.decrypt_block_mut(GenericArray::from_mut_slice(byte));
+            }
+            self.decrypted = src.len();
+        }
+
+        let Some((length, prefix_length)) = peek_varint(src)? else {
+            return Ok(None);
+        };
+
+        if length < 0 || length as usize > self.max_frame_length {
+            return Err(invalid("Invalid packet length"));
+        }
+
+        let frame_end = prefix_length + length as usize;
+        if src.len() < frame_end {
+            src.reserve(frame_end - src.len());
+            return Ok(None);
+        }
+
+        src.advance(prefix_length);
+        let frame = src.split_to(length as usize);
+        self.decrypted = self.decrypted.saturating_sub(frame_end);
+
+        match self.compression {
+            Some(threshold) => self.decompress(frame, threshold).map(Some),
+            None => Ok(Some(frame)),
+        }
+    }
+}
+
+impl<T: ToBytes> Encoder<T> for FrameCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> io::Result<()> {
+        let start = dst.len();
+
+        // Nothing of a packet that failed to encode may be left to be sent
+        if let Err(e) = self.write_frame(&item, dst) {
+            dst.truncate(start);
+            return Err(e);
+        }
+
+        if let Some((encryptor, _)) = &mut self.cipher {
+            for byte in dst[start..].chunks_exact_mut(1) {
+                encryptor.encrypt_block_mut(GenericArray::from_mut_slice(byte));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A [`FrameCodec`] that parses the frames as packets of type `P`
+///
+/// The packet type changes with the connection state. Use [`PacketCodec::map`]
+/// together with `Framed::map_codec` to switch it without losing buffered data.
+pub struct PacketCodec<P> {
+    frames: FrameCodec,
+    _packet: PhantomData<fn() -> P>,
+}
+
+impl<P> PacketCodec<P> {
+    pub fn new() -> Self {
+        Self::from_frame_codec(FrameCodec::new())
+    }
+    pub fn from_frame_codec(frames: FrameCodec) -> Self {
+        Self {
+            frames,
+            _packet: PhantomData,
+        }
+    }
+    /// Switches to parsing a different type of packets, keeping the compression
+    /// and encryption state
+    pub fn map<Q>(self) -> PacketCodec<Q> {
+        PacketCodec::from_frame_codec(self.frames)
+    }
+    pub fn frames(&self) -> &FrameCodec {
+        &self.frames
+    }
+    pub fn frames_mut(&mut self) -> &mut FrameCodec {
+        &mut self.frames
+    }
+    pub fn into_frame_codec(self) -> FrameCodec {
+        self.frames
+    }
+}
+
+impl<P> Default for PacketCodec<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: FromBytes> Decoder for PacketCodec<P> {
+    type Item = P;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<P>> {
+        match self.frames.decode(src)? {
+            Some(frame) => {
+                let mut slice = &frame[..];
+
+                limits::with_limits(DecodeLimits::for_frame(frame.len()), || {
+                    P::read_from(&mut slice)
+                })
+                .map(Some)
+                .map_err(|e| DecodeError::at_offset(e, frame.len() - slice.len()))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl<P, T: ToBytes> Encoder<T> for PacketCodec<P> {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> io::Result<()> {
+        self.frames.encode(item, dst)
+    }
+}
+
+/// Reads a VarInt from the start of `src` without consuming it
+///
+/// Returns the value and its length, or `None` if it's not complete yet
+fn peek_varint(src: &[u8]) -> io::Result<Option<(i32, usize)>> {
+    let mut result = 0i32;
+
+    for (i, byte) in src.iter().enumerate() {
+        // VarInts are at most 5 bytes long.
+        if i == 5 {
+            return Err(invalid("VarInt is too big"));
+        }
+
+        result |= ((byte & 0b0111_1111) as i32) << (7 * i);
+
+        if byte & 0b1000_0000 == 0 {
+            return Ok(Some((result, i + 1)));
+        }
+    }
+
+    Ok(None)
+}
+
+fn put_varint(dst: &mut BytesMut, value: usize) {
+    // Writing to a BytesMut can't fail
+    let _ = VarInt(value as i32).write_to(&mut dst.writer());
+}
+
+/// Writes `item` to `dst`, making sure it's as long as its `encoded_size` said
+fn write_exact<T: ToBytes>(item: &T, dst: &mut BytesMut, length: usize) -> io::Result<()> {
+    let written = item.write_to(&mut dst.writer())?;
+
+    if written != length {
+        return Err(size_mismatch());
+    }
+
+    Ok(())
+}
+
+fn size_mismatch() -> io::Error {
+    io::Error::other("Packet size doesn't match the written length")
+}
+
+fn check_length(length: usize, max: usize) -> io::Result<()> {
+    if length > max {
+        return Err(invalid("Packet too long"));
+    }
+
+    Ok(())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FrameCodec, PacketCodec, MAX_UNCOMPRESSED_LENGTH};
+    use crate::{ToBytes, VarInt};
+    use bytes::BytesMut;
+    use std::io::{self, Write};
+    use tokio_util::codec::{Decoder, Encoder};
+
+    #[test]
+    fn compressed_round_trip() {
+        for (compression, length) in [
+            (None, 10),
+            (None, 1000),
+            (Some(256), 10),
+            (Some(256), 1000),
+            (Some(0), 0),
+        ] {
+            let packet = vec![7u8; length];
+
+            let mut codec = PacketCodec::<Vec<u8>>::new();
+            if let Some(threshold) = compression {
+                codec.frames_mut().enable_compression(threshold);
+            }
+
+            let mut stream = BytesMut::new();
+            codec.encode(&packet, &mut stream).unwrap();
+
+            if compression.is_some() && length >= 256 {
+                // should actually be compressed
+                assert!(stream.len() < length);
+            }
+
+            // Only part of the frame arrived
+            let mut partial = stream.split_to(stream.len() / 2);
+            assert!(codec.decode(&mut partial).unwrap().is_none());
+
+            partial.unsplit(stream);
+            assert_eq!(codec.decode(&mut partial).unwrap().unwrap(), packet);
+            assert!(partial.is_empty());
+        }
+    }
+
+    #[test]
+    fn encryption_mid_stream() {
+        let key = [3u8; 16];
+
+        let mut writer = FrameCodec::new();
+        let mut stream = BytesMut::new();
+        writer.encode(&b"plain".to_vec(), &mut stream).unwrap();
+        writer.enable_encryption(&key);
+        writer.encode(&b"secret".to_vec(), &mut stream).unwrap();
+        writer.encode(&b"more".to_vec(), &mut stream).unwrap();
+
+        assert_eq!(&stream[..7], b"\x06\x05plain");
+        assert!(!stream.windows(6).any(|w| w == b"secret"));
+
+        // Everything arrived at once, before encryption was enabled
+        let mut reader = PacketCodec::<Vec<u8>>::new();
+        assert_eq!(reader.decode(&mut stream).unwrap().unwrap(), b"plain");
+
+        reader.frames_mut().enable_encryption(&key);
+        assert_eq!(reader.decode(&mut stream).unwrap().unwrap(), b"secret");
+        assert_eq!(reader.decode(&mut stream).unwrap().unwrap(), b"more");
+        assert!(reader.decode(&mut stream).unwrap().is_none());
+    }
+
+    /// Writes part of itself before failing, or writes fewer bytes than its size
+    struct Broken {
+        fails: bool,
+    }
+
+    impl ToBytes for Broken {
+        fn write_to<W: Write>(&self, write: &mut W) -> io::Result<usize> {
+            write.write_all(&[1; 300])?;
+            match self.fails {
+                true => Err(io::ErrorKind::InvalidInput.into()),
+                false => Ok(300),
+            }
+        }
+        fn encoded_size(&self) -> usize {
+            301
+        }
+    }
+
+    #[test]
+    fn failed_encode_leaves_nothing() {
+        for compression in [None, Some(1000), Some(256)] {
+            let mut writer = FrameCodec::new();
+            let mut reader = PacketCodec::<Vec<u8>>::new();
+            if let Some(threshold) = compression {
+                writer.enable_compression(threshold);
+                reader.frames_mut().enable_compression(threshold);
+            }
+            writer.enable_encryption(&[3; 16]);
+            reader.frames_mut().enable_encryption(&[3; 16]);
+
+            let mut stream = BytesMut::new();
+            for fails in [true, false] {
+                assert!(writer.encode(Broken { fails }, &mut stream).is_err());
+                assert!(stream.is_empty());
+            }
+
+            // The stream is still usable
+            writer.encode(&b"after".to_vec(), &mut stream).unwrap();
+            assert_eq!(reader.decode(&mut stream).unwrap().unwrap(), b"after");
+        }
+    }
+
+    #[test]
+    fn invalid_frames() {
+        // Frame longer than the maximum
+        let mut codec = FrameCodec::new().max_frame_length(16);
+        let mut stream = BytesMut::from(&[17u8][..]);
+        assert!(codec.decode(&mut stream).is_err());
+
+        // Decompression bomb
+        let mut frame = Vec::new();
+        VarInt(MAX_UNCOMPRESSED_LENGTH as i32 + 1)
+            .write_to(&mut frame)
+            .unwrap();
+        frame.extend_from_slice(&[0x78, 0x9C, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01]);
+
+        let mut stream = Vec::new();
+        VarInt(frame.len() as i32).write_to(&mut stream).unwrap();
+        stream.extend_from_slice(&frame);
+
+        let mut codec = FrameCodec::new();
+        codec.enable_compression(256);
+        assert!(codec.decode(&mut BytesMut::from(&stream[..])).is_err());
+    }
+}
@@ -0,0 +1,433 @@
+//! Field encodings that differ from the field type's own, for the derives
+//!
+//! Fields of derived types are encoded by their type, unless they have one of these
+//! attributes:
+//!
+//! - `#[prefixed(u8)]`, `#[prefixed(u16)]`, `#[prefixed(VarInt)]` for the length prefix of
+//!   a `Vec<T>`, `Box<[T]>` or `String`, or `#[prefixed(none)]` for one that takes up the
+//!   rest of the packet, so it can only be the last field
+//! - `#[varint]` and `#[varlong]` for `i32` and `i64`
+//! - `#[angle]` for an `f32` in degrees, sent as steps of 1/256 of a full turn
+//! - `#[fixed_point(N)]` for an `f64`, sent as an `i32` with `N` fractional bits
+//! - `#[skip]` for a field that isn't sent, and is `Default::default()` when read, or
+//!   `#[skip(expr)]` to use another value
+//! - `#[when(condition)]` for an `Option<T>` field that's only present when the condition
+//!   holds, instead of being prefixed with a bool. The condition can refer to the named
+//!   fields before it, as references. Writing fails if the condition holds but the field
+//!   is `None`, and the field isn't written if it doesn't hold.
+//!
+//! Fixed-size arrays `[T; N]` don't need an attribute, as they are never length-prefixed.
+
+use crate::{limits, DecodeError, FromBytes, ToBytes, VarInt};
+use std::{
+    io::{Error, ErrorKind, Read, Result, Write},
+    marker::PhantomData,
+};
+
+/// An alternative encoding for `T`
+pub trait Encoding<T> {
+    fn read<R: Read>(read: &mut R) -> Result<T>;
+    fn write<W: Write>(value: &T, write: &mut W) -> Result<usize>;
+    /// The exact number of bytes `write` would write
+    fn size(value: &T) -> usize;
+}
+
+/// Length-prefixed by `L` instead of a VarInt
+pub struct Prefixed<L>(PhantomData<L>);
+
+/// Not length-prefixed, taking up the rest of the packet
+pub struct Rest;
+
+/// A VarInt instead of a fixed-size integer
+pub struct AsVarInt;
+
+/// A VarLong instead of a fixed-size integer
+pub struct AsVarLong;
+
+/// An angle in degrees, as steps of 1/256 of a full turn
+pub struct Angle;
+
+/// A fixed-point number with `BITS` fractional bits, as an `i32`
+pub struct FixedPoint<const BITS: u32>;
+
+/// A type that lengths can be prefixed with
+pub trait LengthPrefix {
+    /// Reads a length, and checks it against the [`limits`]
+    fn read_length<R: Read>(read: &mut R) -> Result<usize>;
+    fn write_length<W: Write>(length: usize, write: &mut W) -> Result<usize>;
+    fn length_size(length: usize) -> usize;
+}
+
+macro_rules! impl_length_prefix {
+    ( $( $type:ty ),+ ) => {
+        $(
+            impl LengthPrefix for $type {
+                fn read_length<R: Read>(read: &mut R) -> Result<usize> {
+                    let length = <$type>::read_from(read)? as usize;
+                    limits::check_length(length)?;
+
+                    Ok(length)
+                }
+                fn write_length<W: Write>(length: usize, write: &mut W) -> Result<usize> {
+                    let length = <$type>::try_from(length).map_err(|_| too_long())?;
+
+                    length.write_to(write)
+                }
+                fn length_size(_: usize) -> usize {
+                    std::mem::size_of::<$type>()
+                }
+            }
+        )+
+    };
+}
+
+impl_length_prefix! { u8, u16 }
+
+impl LengthPrefix for VarInt {
+    fn read_length<R: Read>(read: &mut R) -> Result<usize> {
+        let length = VarInt::read_from(read)?.0;
+
+        if length < 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "Negative length"));
+        }
+        limits::check_length(length as usize)?;
+
+        Ok(length as usize)
+    }
+    fn write_length<W: Write>(length: usize, write: &mut W) -> Result<usize> {
+        let length = i32::try_from(length).map_err(|_| too_long())?;
+
+        VarInt(length).write_to(write)
+    }
+    fn length_size(length: usize) -> usize {
+        VarInt(length as i32).encoded_size()
+    }
+}
+
+impl<L: LengthPrefix, T: FromBytes + ToBytes> Encoding<Vec<T>> for Prefixed<L> {
+    fn read<R: Read>(read: &mut R) -> Result<Vec<T>> {
+        let length = L::read_length(read)?;
+        limits::allocate::<T>(length)?;
+
+        let mut buffer = Vec::with_capacity(length);
+
+        for i in 0..length {
+            buffer.push(T::read_from(read).map_err(|e| DecodeError::in_element(e, i))?);
+        }
+
+        Ok(buffer)
+    }
+    fn write<W: Write>(value: &Vec<T>, write: &mut W) -> Result<usize> {
+        Ok(L::write_length(value.len(), write)? + write_elements(value, write)?)
+    }
+    fn size(value: &Vec<T>) -> usize {
+        L::length_size(value.len()) + elements_size(value)
+    }
+}
+
+impl<L: LengthPrefix, T: FromBytes + ToBytes> Encoding<Box<[T]>> for Prefixed<L> {
+    fn read<R: Read>(read: &mut R) -> Result<Box<[T]>> {
+        Ok(<Self as Encoding<Vec<T>>>::read(read)?.into_boxed_slice())
+    }
+    fn write<W: Write>(value: &Box<[T]>, write: &mut W) -> Result<usize> {
+        Ok(L::write_length(value.len(), write)? + write_elements(value, write)?)
+    }
+    fn size(value: &Box<[T]>) -> usize {
+        L::length_size(value.len()) + elements_size(value)
+    }
+}
+
+impl<L: LengthPrefix> Encoding<String> for Prefixed<L> {
+    fn read<R: Read>(read: &mut R) -> Result<String> {
+        let length = L::read_length(read)?;
+        limits::allocate::<u8>(length)?;
+
+        let mut buffer = Vec::new();
+        read.take(length as u64).read_to_end(&mut buffer)?;
+
+        if buffer.len() != length {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+
+        into_string(buffer)
+    }
+    fn write<W: Write>(value: &String, write: &mut W) -> Result<usize> {
+        let written = L::write_length(value.len(), write)?;
+        write.write_all(value.as_bytes())?;
+
+        Ok(written + value.len())
+    }
+    fn size(value: &String) -> usize {
+        L::length_size(value.len()) + value.len()
+    }
+}
+
+impl<T: FromBytes + ToBytes> Encoding<Vec<T>> for Rest {
+    fn read<R: Read>(read: &mut R) -> Result<Vec<T>> {
+        Ok(Box::<[T]>::read_from(read)?.into_vec())
+    }
+    fn write<W: Write>(value: &Vec<T>, write: &mut W) -> Result<usize> {
+        write_elements(value, write)
+    }
+    fn size(value: &Vec<T>) -> usize {
+        elements_size(value)
+    }
+}
+
+impl<T: FromBytes + ToBytes> Encoding<Box<[T]>> for Rest {
+    fn read<R: Read>(read: &mut R) -> Result<Box<[T]>> {
+        Box::<[T]>::read_from(read)
+    }
+    fn write<W: Write>(value: &Box<[T]>, write: &mut W) -> Result<usize> {
+        value.write_to(write)
+    }
+    fn size(value: &Box<[T]>) -> usize {
+        value.encoded_size()
+    }
+}
+
+impl Encoding<String> for Rest {
+    fn read<R: Read>(read: &mut R) -> Result<String> {
+        into_string(<Self as Encoding<Vec<u8>>>::read(read)?)
+    }
+    fn write<W: Write>(value: &String, write: &mut W) -> Result<usize> {
+        write.write_all(value.as_bytes())?;
+
+        Ok(value.len())
+    }
+    fn size(value: &String) -> usize {
+        value.len()
+    }
+}
+
+impl Encoding<i32> for AsVarInt {
+    fn read<R: Read>(read: &mut R) -> Result<i32> {
+        Ok(VarInt::read_from(read)?.0)
+    }
+    fn write<W: Write>(value: &i32, write: &mut W) -> Result<usize> {
+        VarInt(*value).write_to(write)
+    }
+    fn size(value: &i32) -> usize {
+        VarInt(*value).encoded_size()
+    }
+}
+
+impl Encoding<i64> for AsVarLong {
+    fn read<R: Read>(read: &mut R) -> Result<i64> {
+        Ok(crate::newtypes::VarLong::read_from(read)?.0)
+    }
+    fn write<W: Write>(value: &i64, write: &mut W) -> Result<usize> {
+        crate::newtypes::VarLong(*value).write_to(write)
+    }
+    fn size(value: &i64) -> usize {
+        crate::newtypes::VarLong(*value).encoded_size()
+    }
+}
+
+impl Encoding<f32> for Angle {
+    fn read<R: Read>(read: &mut R) -> Result<f32> {
+        Ok(crate::newtypes::Angle::read_from(read)?.degrees())
+    }
+    fn write<W: Write>(value: &f32, write: &mut W) -> Result<usize> {
+        crate::newtypes::Angle::from_degrees(*value).write_to(write)
+    }
+    fn size(_: &f32) -> usize {
+        1
+    }
+}
+
+impl<const BITS: u32> Encoding<f64> for FixedPoint<BITS> {
+    fn read<R: Read>(read: &mut R) -> Result<f64> {
+        Ok(i32::read_from(read)? as f64 / (1u64 << BITS) as f64)
+    }
+    fn write<W: Write>(value: &f64, write: &mut W) -> Result<usize> {
+        ((value * (1u64 << BITS) as f64) as i32).write_to(write)
+    }
+    fn size(_: &f64) -> usize {
+        4
+    }
+}
+
+fn write_elements<T: ToBytes, W: Write>(elements: &[T], write: &mut W) -> Result<usize> {
+    let mut written = 0;
+
+    for e in elements {
+        written += e.write_to(write)?;
+    }
+
+    Ok(written)
+}
+
+fn elements_size<T: ToBytes>(elements: &[T]) -> usize {
+    elements.iter().map(ToBytes::encoded_size).sum()
+}
+
+fn into_string(bytes: Vec<u8>) -> Result<String> {
+    String::from_utf8(bytes)
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "String not valid UTF-8"))
+}
+
+fn too_long() -> Error {
+    Error::new(ErrorKind::InvalidInput, "Too long for the length prefix")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FromBytes, ToBytes};
+
+    #[derive(FromBytes, ToBytes, Debug, PartialEq)]
+    struct Custom {
+        #[prefixed(u8)]
+        name: String,
+        #[prefixed(u16)]
+        ids: Vec<u16>,
+        #[varint]
+        count: i32,
+        #[varlong]
+        time: i64,
+        #[angle]
+        yaw: f32,
+        #[fixed_point(5)]
+        x: f64,
+        #[skip]
+        cached: Option<u32>,
+        #[skip(7)]
+        seven: u8,
+        key: [u8; 4],
+        #[prefixed(none)]
+        data: Vec<u8>,
+    }
+
+    #[derive(FromBytes, ToBytes, Debug, PartialEq)]
+    enum Action {
+        Add {
+            flags: u8,
+            #[when(flags & 0x01 != 0)]
+            name: Option<String>,
+            #[when(flags & 0x02 != 0)]
+            #[varint]
+            latency: Option<i32>,
+        },
+        Remove(u8),
+    }
+
+    #[derive(FromBytes, ToBytes, Debug, PartialEq)]
+    struct Team {
+        action: Action,
+        #[when(matches!(action, Action::Add { .. }))]
+        #[prefixed(u8)]
+        members: Option<Vec<u8>>,
+    }
+
+    /// Serverbound, so only read
+    #[derive(FromBytes, Debug, PartialEq)]
+    struct Interact {
+        #[varint]
+        kind: i32,
+        #[when(*kind == 2)]
+        target: Option<[f32; 3]>,
+    }
+
+    #[test]
+    fn conditional_fields() {
+        let samples: &[(Team, &[u8])] = &[
+            (
+                Team {
+                    action: Action::Add {
+                        flags: 0x03,
+                        name: Some("a".to_string()),
+                        latency: Some(300),
+                    },
+                    members: Some(vec![9]),
+                },
+                b"\x00\x03\x01a\xAC\x02\x01\x09",
+            ),
+            (
+                Team {
+                    action: Action::Add {
+                        flags: 0x02,
+                        name: None,
+                        latency: Some(1),
+                    },
+                    members: Some(vec![]),
+                },
+                b"\x00\x02\x01\x00",
+            ),
+            (
+                Team {
+                    action: Action::Remove(5),
+                    members: None,
+                },
+                b"\x01\x05",
+            ),
+        ];
+
+        for (team, expected) in samples {
+            let mut bytes = Vec::new();
+            assert_eq!(team.write_to(&mut bytes).unwrap(), team.encoded_size());
+            assert_eq!(bytes, *expected);
+            assert_eq!(&Team::read_from(&mut &bytes[..]).unwrap(), team);
+        }
+
+        // The flag says there's a name, but there isn't
+        let missing = Action::Add {
+            flags: 0x01,
+            name: None,
+            latency: None,
+        };
+        assert!(missing.write_to(&mut Vec::new()).is_err());
+
+        let interact = Interact::read_from(&mut &b"\x02\x3F\x80\0\0\0\0\0\0\0\0\0\0"[..]);
+        assert_eq!(
+            interact.unwrap(),
+            Interact {
+                kind: 2,
+                target: Some([1.0, 0.0, 0.0]),
+            }
+        );
+        let interact = Interact::read_from(&mut &b"\x00"[..]).unwrap();
+        assert_eq!(interact.target, None);
+    }
+
+    #[test]
+    fn field_attributes() {
+        let custom = Custom {
+            name: "abc".to_string(),
+            ids: vec![1, 2],
+            count: 300,
+            time: -1,
+            yaw: 90.0,
+            x: 1.5,
+            cached: Some(5),
+            seven: 7,
+            key: [1, 2, 3, 4],
+            data: b"rest".to_vec(),
+        };
+
+        let mut bytes = Vec::new();
+        let written = custom.write_to(&mut bytes).unwrap();
+        assert_eq!(written, bytes.len());
+        assert_eq!(custom.encoded_size(), written);
+
+        let mut expected = b"\x03abc\x00\x02\x00\x01\x00\x02\xAC\x02".to_vec();
+        expected.extend_from_slice(&[0xFF; 9]);
+        expected.extend_from_slice(b"\x01\x40\x00\x00\x00\x30\x01\x02\x03\x04rest");
+        assert_eq!(bytes, expected);
+
+        let read = Custom::read_from(&mut &bytes[..]).unwrap();
+        assert_eq!(
+            read,
+            Custom {
+                cached: None,
+                ..custom
+            }
+        );
+
+        // Doesn't fit the prefix
+        let long = Custom {
+            name: "a".repeat(256),
+            ..read
+        };
+        assert!(long.write_to(&mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+//! Decode errors with context about where in a packet they happened
+
+use std::{error::Error, fmt, io};
+
+/// An error while decoding a packet, with the path to the field that failed
+///
+/// Decoding still returns `io::Error`s, with this as the inner error and the same
+/// kind as the original error. Use [`DecodeError::from_io`] to get it back.
+#[derive(Debug)]
+pub struct DecodeError {
+    type_name: Option<&'static str>,
+    /// Outermost first
+    path: Vec<PathSegment>,
+    offset: Option<usize>,
+    source: io::Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathSegment {
+    Variant(&'static str),
+    Field(&'static str),
+    Index(usize),
+}
+
+impl DecodeError {
+    /// Extracts the decode error from an `io::Error`, or wraps it in one without any context
+    pub fn from_io(error: io::Error) -> Self {
+        if !error.get_ref().is_some_and(|inner| inner.is::<Self>()) {
+            return Self {
+                type_name: None,
+                path: Vec::new(),
+                offset: None,
+                source: error,
+            };
+        }
+
+        // Checked above
+        *error.into_inner().unwrap().downcast::<Self>().unwrap()
+    }
+    /// Adds the field the error happened in
+    pub fn in_field(error: io::Error, type_name: &'static str, field: &'static str) -> io::Error {
+        Self::from_io(error)
+            .with_segment(type_name, PathSegment::Field(field))
+            .into()
+    }
+    /// Adds the enum variant the error happened in
+    pub fn in_variant(
+        error: io::Error,
+        type_name: &'static str,
+        variant: &'static str,
+    ) -> io::Error {
+        Self::from_io(error)
+            .with_segment(type_name, PathSegment::Variant(variant))
+            .into()
+    }
+    /// Adds the index of the sequence element the error happened in
+    pub fn in_element(error: io::Error, index: usize) -> io::Error {
+        let mut e = Self::from_io(error);
+        e.path.insert(0, PathSegment::Index(index));
+        e.into()
+    }
+    /// Sets the type the error happened in, if it isn't set yet
+    pub fn in_type(error: io::Error, type_name: &'static str) -> io::Error {
+        let mut e = Self::from_io(error);
+        e.type_name.get_or_insert(type_name);
+        e.into()
+    }
+    /// An unknown enum discriminant
+    pub fn invalid_discriminant(type_name: &'static str, discriminant: i32) -> io::Error {
+        Self::in_type(
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid enum discriminant {discriminant}"),
+            ),
+            type_name,
+        )
+    }
+    /// Sets the byte offset in the packet, if it isn't set yet
+    pub fn at_offset(error: io::Error, offset: usize) -> io::Error {
+        let mut e = Self::from_io(error);
+        e.offset.get_or_insert(offset);
+        e.into()
+    }
+
+    /// The outermost type that was being decoded
+    pub fn type_name(&self) -> Option<&'static str> {
+        self.type_name
+    }
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+    /// The offset in the packet where decoding failed
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+    /// The error without context
+    pub fn reason(&self) -> &io::Error {
+        &self.source
+    }
+
+    fn with_segment(mut self, type_name: &'static str, segment: PathSegment) -> Self {
+        self.path.insert(0, segment);
+        self.type_name = Some(type_name);
+        self
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to decode ")?;
+
+        match self.type_name {
+            Some(type_name) => write!(f, "{type_name}")?,
+            None => write!(f, "packet")?,
+        }
+        for segment in &self.path {
+            match segment {
+                PathSegment::Variant(variant) => write!(f, "::{variant}")?,
+                PathSegment::Field(field) => write!(f, ".{field}")?,
+                PathSegment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " at byte {offset}")?;
+        }
+
+        write!(f, ": {}", self.source)
+    }
+}
+
+impl Error for DecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<DecodeError> for io::Error {
+    fn from(error: DecodeError) -> Self {
+        io::Error::new(error.source.kind(), error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecodeError, PathSegment};
+    use crate::{packets::SBLogin, FromBytes};
+
+    #[test]
+    fn field_path() {
+        // LoginStart with a name that's not UTF-8
+        let bytes = [0x00, 0x02, 0xC3, 0x28, 0x00];
+
+        let error = SBLogin::read_from(&mut &bytes[..]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        let error = DecodeError::from_io(DecodeError::at_offset(error, 1));
+        assert_eq!(error.type_name(), Some("SBLogin"));
+        assert_eq!(
+            error.path(),
+            [
+                PathSegment::Variant("LoginStart"),
+                PathSegment::Field("name")
+            ]
+        );
+        assert_eq!(
+            error.to_string(),
+            "Failed to decode SBLogin::LoginStart.name at byte 1: String not valid UTF-8"
+        );
+
+        let error = DecodeError::from_io(SBLogin::read_from(&mut &[0x7F][..]).unwrap_err());
+        assert_eq!(
+            error.to_string(),
+            "Failed to decode SBLogin: Invalid enum discriminant 127"
+        );
+
+        // Plain errors are wrapped
+        let error: std::io::Error = std::io::ErrorKind::UnexpectedEof.into();
+        assert!(DecodeError::from_io(error).type_name().is_none());
+    }
+}
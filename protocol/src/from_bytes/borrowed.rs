@@ -0,0 +1,140 @@
+use super::FromBytesBorrowed;
+use crate::{limits, newtypes::TrailingBytes, DecodeError, FromBytes, VarInt};
+use std::io::{Error, ErrorKind, Result};
+
+impl<'a> FromBytesBorrowed<'a> for &'a [u8] {
+    fn read_borrowed(read: &mut &'a [u8]) -> Result<Self> {
+        let length = VarInt::read_from(read)?.0;
+
+        if length < 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "Negative length"));
+        }
+        limits::check_length(length as usize)?;
+        if length as usize > read.len() {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+
+        let (bytes, rest) = read.split_at(length as usize);
+        *read = rest;
+
+        Ok(bytes)
+    }
+}
+
+impl<'a> FromBytesBorrowed<'a> for &'a str {
+    fn read_borrowed(read: &mut &'a [u8]) -> Result<Self> {
+        let bytes = <&[u8]>::read_borrowed(read)?;
+
+        std::str::from_utf8(bytes)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "String not valid UTF-8"))
+    }
+}
+
+impl<'a> FromBytesBorrowed<'a> for TrailingBytes<'a> {
+    fn read_borrowed(read: &mut &'a [u8]) -> Result<Self> {
+        Ok(Self(std::mem::take(read)))
+    }
+}
+
+impl<'a, T: FromBytesBorrowed<'a>> FromBytesBorrowed<'a> for Option<T> {
+    fn read_borrowed(read: &mut &'a [u8]) -> Result<Self> {
+        if bool::read_from(read)? {
+            Ok(Some(T::read_borrowed(read)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<'a, T: FromBytesBorrowed<'a>> FromBytesBorrowed<'a> for Vec<T> {
+    fn read_borrowed(read: &mut &'a [u8]) -> Result<Self> {
+        let length = VarInt::read_from(read)?.0;
+
+        if length < 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "Negative length"));
+        }
+        limits::check_length(length as usize)?;
+        limits::allocate::<T>(length as usize)?;
+
+        (0..length as usize)
+            .map(|i| T::read_borrowed(read).map_err(|e| DecodeError::in_element(e, i)))
+            .collect()
+    }
+}
+
+impl<'a, T: FromBytesBorrowed<'a>> FromBytesBorrowed<'a> for Box<T> {
+    fn read_borrowed(read: &mut &'a [u8]) -> Result<Self> {
+        Ok(Box::new(T::read_borrowed(read)?))
+    }
+}
+
+impl<'a, T: FromBytesBorrowed<'a>> FromBytesBorrowed<'a> for Box<[T]> {
+    fn read_borrowed(read: &mut &'a [u8]) -> Result<Self> {
+        let max_elements = limits::max_elements();
+        let mut res = Vec::new();
+
+        // Until the end of the packet
+        while !read.is_empty() {
+            if res.len() == max_elements {
+                return Err(Error::new(ErrorKind::InvalidData, "Too many elements"));
+            }
+            limits::allocate::<T>(1)?;
+            res.push(T::read_borrowed(read)?);
+        }
+
+        Ok(res.into_boxed_slice())
+    }
+}
+
+impl<'a, T: FromBytesBorrowed<'a>, const N: usize> FromBytesBorrowed<'a> for [T; N] {
+    fn read_borrowed(read: &mut &'a [u8]) -> Result<Self> {
+        let elements = (0..N)
+            .map(|i| T::read_borrowed(read).map_err(|e| DecodeError::in_element(e, i)))
+            .collect::<Result<Vec<T>>>()?;
+
+        // Exactly N elements were read
+        Ok(elements.try_into().unwrap_or_else(|_| unreachable!()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        newtypes::{Position, TrailingBytes},
+        FromBytes, FromBytesBorrowed, ToBytes, VarInt,
+    };
+
+    #[derive(FromBytes, ToBytes, Debug, PartialEq)]
+    struct PluginMessage<'a> {
+        channel: &'a str,
+        // Fields without the lifetime are read with `FromBytes`
+        id: VarInt,
+        origins: Option<Vec<Position>>,
+        data: TrailingBytes<'a>,
+    }
+
+    #[test]
+    fn borrowed_read_and_write() {
+        let message = PluginMessage {
+            channel: "minecraft:brand",
+            id: VarInt(300),
+            origins: Some(vec![Position::new(1, 2, 3)]),
+            data: TrailingBytes(b"\x07vanilla"),
+        };
+
+        let mut bytes = Vec::new();
+        message.write_to(&mut bytes).unwrap();
+        assert_eq!(message.encoded_size(), bytes.len());
+
+        let mut slice = &bytes[..];
+        let read = PluginMessage::read_borrowed(&mut slice).unwrap();
+        assert_eq!(read, message);
+        assert!(slice.is_empty());
+
+        // Borrowed straight from the buffer
+        assert_eq!(read.channel.as_ptr(), bytes[1..].as_ptr());
+
+        // Not enough data
+        assert!(<&str>::read_borrowed(&mut &b"\x05abc"[..]).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{newtypes::VarLong, FromBytes, VarInt};
+use crate::{limits, newtypes::VarLong, DecodeError, FromBytes, VarInt};
 use serde_json::Value;
 use std::io::{ErrorKind, Read, Result};
 use uuid::Uuid;
@@ -12,11 +12,21 @@ impl<T: FromBytes> FromBytes for Box<T> {
 impl<T: FromBytes> FromBytes for Box<[T]> {
     fn read_from<R: Read>(read: &mut R) -> Result<Self> {
         // Todo maybe error if EOF mid-element
+        let max_elements = limits::max_elements();
         let mut res = Vec::new();
 
         loop {
             match T::read_from(read) {
-                Ok(element) => res.push(element),
+                Ok(_) if res.len() == max_elements => {
+                    return Err(std::io::Error::new(
+                        ErrorKind::InvalidData,
+                        "Too many elements",
+                    ))
+                }
+                Ok(element) => {
+                    limits::allocate::<T>(1)?;
+                    res.push(element);
+                }
                 Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(res.into_boxed_slice()),
                 Err(e) => return Err(e),
             }
@@ -51,13 +61,18 @@ impl FromBytes for String {
     where
         Self: Sized,
     {
-        let length = VarInt::read_from(read)?;
+        let length = read_length(read)?;
+        limits::allocate::<u8>(length)?;
 
-        let mut buffer = vec![0u8; length.0 as usize];
+        // Grows as the data arrives, so a bogus length can't allocate a huge buffer up front
+        let mut buffer = Vec::new();
+        read.take(length as u64).read_to_end(&mut buffer)?;
 
-        read.read_exact(&mut buffer[..])?;
+        if buffer.len() != length {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
 
-        let string = String::from_utf8(buffer.to_vec()).map_err(|e| {
+        let string = String::from_utf8(buffer).map_err(|_| {
             std::io::Error::new(std::io::ErrorKind::InvalidData, "String not valid UTF-8")
         })?;
 
@@ -154,18 +169,31 @@ impl<T: FromBytes> FromBytes for Vec<T> {
     where
         Self: Sized,
     {
-        let length = VarInt::read_from(read)?;
+        let length = read_length(read)?;
+        limits::allocate::<T>(length)?;
 
-        let mut buffer = Vec::with_capacity(length.0 as usize);
+        let mut buffer = Vec::with_capacity(length);
 
-        for _ in 0..length.0 {
-            buffer.push(T::read_from(read)?);
+        for i in 0..length {
+            buffer.push(T::read_from(read).map_err(|e| DecodeError::in_element(e, i))?);
         }
 
         Ok(buffer)
     }
 }
 
+/// Fixed-size, so not length-prefixed
+impl<T: FromBytes, const N: usize> FromBytes for [T; N] {
+    fn read_from<R: Read>(read: &mut R) -> Result<Self> {
+        let elements = (0..N)
+            .map(|i| T::read_from(read).map_err(|e| DecodeError::in_element(e, i)))
+            .collect::<Result<Vec<T>>>()?;
+
+        // Exactly N elements were read
+        Ok(elements.try_into().unwrap_or_else(|_| unreachable!()))
+    }
+}
+
 impl FromBytes for bool {
     fn read_from<R: Read>(read: &mut R) -> Result<bool> {
         let mut buf = [0u8; 1];
@@ -175,6 +203,22 @@ impl FromBytes for bool {
     }
 }
 
+/// Reads a VarInt length prefix, rejecting negative lengths and ones over the limits
+fn read_length<R: Read>(read: &mut R) -> Result<usize> {
+    let length = VarInt::read_from(read)?.0;
+
+    if length < 0 {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            "Negative length",
+        ));
+    }
+
+    limits::check_length(length as usize)?;
+
+    Ok(length as usize)
+}
+
 macro_rules! impl_from_bytes {
     ( $( $primitive:ty ),+ )  => {
         $(
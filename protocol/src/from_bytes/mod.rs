@@ -1,5 +1,6 @@
 use std::io::{Read, Result};
 
+mod borrowed;
 mod impls;
 
 pub trait FromBytes {
@@ -7,3 +8,14 @@ pub trait FromBytes {
     where
         Self: Sized;
 }
+
+/// Reading from a packet buffer, borrowing from it instead of allocating
+///
+/// Implemented for `&'a str`, `&'a [u8]`, [`TrailingBytes`](crate::newtypes::TrailingBytes)
+/// and collections of them. Deriving `FromBytes` for a type with a lifetime parameter
+/// implements this instead, reading the fields that use the lifetime with this and the
+/// others with [`FromBytes`].
+pub trait FromBytesBorrowed<'a>: Sized {
+    /// Reads a value from the start of `read`, advancing it past the value
+    fn read_borrowed(read: &mut &'a [u8]) -> Result<Self>;
+}
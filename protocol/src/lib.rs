@@ -3,9 +3,23 @@ extern crate self as protocol;
 mod from_bytes;
 mod to_bytes;
 
+pub mod chunk;
+pub mod codec;
+pub mod encoding;
+pub mod error;
+pub mod limits;
+pub mod nbt;
 pub mod newtypes;
 pub mod packets;
+pub mod text;
+pub mod version;
 
+pub use error::DecodeError;
 pub use newtypes::{BString, VarInt};
-pub use protocol_derive::{FromBytes, ToBytes};
-pub use {from_bytes::FromBytes, to_bytes::ToBytes};
+pub use protocol_derive::{FromBytes, FromBytesVersioned, ToBytes, ToBytesVersioned};
+pub use text::TextComponent;
+pub use {
+    from_bytes::{FromBytes, FromBytesBorrowed},
+    to_bytes::ToBytes,
+    version::{FromBytesVersioned, ProtocolVersion, ToBytesVersioned},
+};
@@ -0,0 +1,206 @@
+//! Limits on how much memory decoding untrusted data can allocate
+//!
+//! Length-prefixed collections check their length against the limits before allocating,
+//! and charge the memory they use to an allocation budget shared by everything decoded
+//! within [`with_limits`]. Outside of it, only the per-collection limits apply.
+//!
+//! Fields of derived types can have a stricter limit with `#[max_len(N)]`, which applies
+//! to the outermost collection or string read for the field. Like the other limits, it's
+//! only checked when reading, and the `ToBytes` derive ignores it.
+
+use std::{
+    cell::Cell,
+    io::{Error, ErrorKind, Result},
+};
+
+/// Limits for decoding one packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Maximum number of elements in a single collection, or bytes in a string
+    pub max_elements: usize,
+    /// Maximum number of bytes all collections and strings together may allocate
+    pub allocation_budget: usize,
+}
+
+impl DecodeLimits {
+    /// Limits for decoding a frame of `length` bytes
+    ///
+    /// Every element takes at least a byte, so no collection can be longer than the frame.
+    pub fn for_frame(length: usize) -> Self {
+        Self {
+            max_elements: length,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_elements: crate::codec::MAX_UNCOMPRESSED_LENGTH,
+            allocation_budget: 4 * crate::codec::MAX_UNCOMPRESSED_LENGTH,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct State {
+    limits: DecodeLimits,
+    remaining_budget: usize,
+}
+
+thread_local! {
+    static STATE: Cell<Option<State>> = const { Cell::new(None) };
+    static FIELD_LIMIT: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Restores the previous state, even if decoding panics
+struct Restore<T: Copy + 'static>(&'static std::thread::LocalKey<Cell<T>>, T);
+
+impl<T: Copy + 'static> Drop for Restore<T> {
+    fn drop(&mut self) {
+        self.0.set(self.1);
+    }
+}
+
+/// Runs `f` with the given limits, charging all allocations during it to one budget
+pub fn with_limits<T>(limits: DecodeLimits, f: impl FnOnce() -> T) -> T {
+    let previous = STATE.replace(Some(State {
+        limits,
+        remaining_budget: limits.allocation_budget,
+    }));
+    let _restore = Restore(&STATE, previous);
+
+    f()
+}
+
+/// Runs `f` with a stricter element limit for the next collection read, used by
+/// `#[max_len(N)]` in the derives
+#[doc(hidden)]
+pub fn with_field_limit<T>(max_elements: usize, f: impl FnOnce() -> T) -> T {
+    let previous = FIELD_LIMIT.replace(Some(max_elements));
+    let _restore = Restore(&FIELD_LIMIT, previous);
+
+    f()
+}
+
+/// The maximum length of the collection that's about to be read
+///
+/// Consumes the field limit, so it doesn't apply to the elements.
+pub(crate) fn max_elements() -> usize {
+    let limit = STATE
+        .get()
+        .map_or_else(DecodeLimits::default, |s| s.limits)
+        .max_elements;
+
+    match FIELD_LIMIT.take() {
+        Some(field_limit) => limit.min(field_limit),
+        None => limit,
+    }
+}
+
+/// Checks the length of a collection that's about to be read
+pub(crate) fn check_length(length: usize) -> Result<()> {
+    if length > max_elements() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Length {length} exceeds the limit"),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Charges `count` elements of `T` to the allocation budget
+pub(crate) fn allocate<T>(count: usize) -> Result<()> {
+    let exceeded = || Error::new(ErrorKind::InvalidData, "Allocation limit exceeded");
+    let bytes = count
+        .checked_mul(std::mem::size_of::<T>())
+        .ok_or_else(exceeded)?;
+
+    match STATE.get() {
+        Some(mut state) => {
+            state.remaining_budget = state
+                .remaining_budget
+                .checked_sub(bytes)
+                .ok_or_else(exceeded)?;
+            STATE.set(Some(state));
+        }
+        None if bytes > DecodeLimits::default().allocation_budget => return Err(exceeded()),
+        None => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{with_limits, DecodeLimits};
+    use crate::{FromBytes, ToBytes};
+
+    #[derive(FromBytes, ToBytes, Debug, PartialEq)]
+    struct Commands {
+        #[max_len(2)]
+        names: Vec<String>,
+        data: Vec<u64>,
+    }
+
+    /// Clientbound, so only written
+    #[derive(ToBytes)]
+    struct Suggestions {
+        #[max_len(2)]
+        matches: Vec<String>,
+    }
+
+    #[test]
+    fn allocation_limits() {
+        // Claims 2^31 - 1 elements in a tiny packet
+        let huge = [0xFF, 0xFF, 0xFF, 0xFF, 0x07, 0x00];
+        assert!(Vec::<u64>::read_from(&mut &huge[..]).is_err());
+        assert!(with_limits(DecodeLimits::for_frame(huge.len()), || {
+            Vec::<u8>::read_from(&mut &huge[..])
+        })
+        .is_err());
+
+        let commands = Commands {
+            names: vec!["a".to_string(), "b".to_string()],
+            data: vec![1, 2, 3],
+        };
+        let mut bytes = Vec::new();
+        commands.write_to(&mut bytes).unwrap();
+        let read = with_limits(DecodeLimits::for_frame(bytes.len()), || {
+            Commands::read_from(&mut &bytes[..])
+        });
+        assert_eq!(read.unwrap(), commands);
+
+        // Field limit
+        let mut bytes = Vec::new();
+        vec!["a", "b", "c"].write_to(&mut bytes).unwrap();
+        0u8.write_to(&mut bytes).unwrap();
+        assert!(Commands::read_from(&mut &bytes[..]).is_err());
+
+        // Not checked when writing
+        let suggestions = Suggestions {
+            matches: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+        };
+        let mut written = Vec::new();
+        suggestions.write_to(&mut written).unwrap();
+        assert_eq!(written, bytes[..bytes.len() - 1]);
+
+        // The budget is shared by the whole packet
+        let limits = DecodeLimits {
+            allocation_budget: 4 * 8,
+            ..DecodeLimits::default()
+        };
+        let mut bytes = Vec::new();
+        Vec::<String>::new().write_to(&mut bytes).unwrap();
+        vec![1u64, 2, 3].write_to(&mut bytes).unwrap();
+        vec![4u64, 5].write_to(&mut bytes).unwrap();
+        let read = with_limits(limits, || {
+            let mut slice = &bytes[..];
+            Commands::read_from(&mut slice)?;
+            Vec::<u64>::read_from(&mut slice)
+        });
+        assert!(read.is_err());
+    }
+}
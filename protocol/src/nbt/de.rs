@@ -0,0 +1,300 @@
+//! Converting NBT to Rust types with serde
+
+use super::{
+    ser::{BYTE_ARRAY, INT_ARRAY, LONG_ARRAY},
+    Compound, Error, Nbt,
+};
+use serde::de::{
+    self, value::StringDeserializer, DeserializeOwned, DeserializeSeed, EnumAccess,
+    IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor,
+};
+use serde::{forward_to_deserialize_any, Deserialize, Deserializer};
+use std::fmt;
+
+/// Converts NBT to a value
+///
+/// Bytes can be read as `bool`s, and numbers as any type they fit into. Missing
+/// compound fields are `None`.
+pub fn from_nbt<T: DeserializeOwned>(nbt: Nbt) -> Result<T, Error> {
+    T::deserialize(nbt)
+}
+
+impl<'de> Deserialize<'de> for Nbt {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(NbtVisitor)
+    }
+}
+
+struct NbtVisitor;
+
+impl<'de> Visitor<'de> for NbtVisitor {
+    type Value = Nbt;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an NBT value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Nbt, E> {
+        Ok(Nbt::Byte(v as i8))
+    }
+    fn visit_i8<E: de::Error>(self, v: i8) -> Result<Nbt, E> {
+        Ok(Nbt::Byte(v))
+    }
+    fn visit_i16<E: de::Error>(self, v: i16) -> Result<Nbt, E> {
+        Ok(Nbt::Short(v))
+    }
+    fn visit_i32<E: de::Error>(self, v: i32) -> Result<Nbt, E> {
+        Ok(Nbt::Int(v))
+    }
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Nbt, E> {
+        Ok(Nbt::Long(v))
+    }
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Nbt, E> {
+        i64::try_from(v)
+            .map(Nbt::Long)
+            .map_err(|_| E::custom(format!("{v} is too large for NBT")))
+    }
+    fn visit_f32<E: de::Error>(self, v: f32) -> Result<Nbt, E> {
+        Ok(Nbt::Float(v))
+    }
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Nbt, E> {
+        Ok(Nbt::Double(v))
+    }
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Nbt, E> {
+        Ok(Nbt::String(v.to_string()))
+    }
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Nbt, E> {
+        Ok(Nbt::String(v))
+    }
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Nbt, E> {
+        Ok(Nbt::ByteArray(v.iter().map(|b| *b as i8).collect()))
+    }
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Nbt, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Nbt, A::Error> {
+        let mut list = Vec::new();
+
+        while let Some(element) = seq.next_element()? {
+            list.push(element);
+        }
+
+        Ok(Nbt::List(list))
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Nbt, A::Error> {
+        let mut compound = Compound::new();
+
+        while let Some((key, value)) = map.next_entry()? {
+            compound.insert(key, value);
+        }
+
+        Ok(Nbt::Compound(compound))
+    }
+    /// Arrays from [`Nbt`]'s deserializer, so they don't become lists
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Nbt, A::Error> {
+        let (name, variant): (String, _) = data.variant()?;
+
+        match name.as_str() {
+            BYTE_ARRAY => variant.newtype_variant().map(Nbt::ByteArray),
+            INT_ARRAY => variant.newtype_variant().map(Nbt::IntArray),
+            LONG_ARRAY => variant.newtype_variant().map(Nbt::LongArray),
+            _ => Err(de::Error::custom("enums can't be NBT")),
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> for Nbt {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Nbt::Byte(v) => visitor.visit_i8(v),
+            Nbt::Short(v) => visitor.visit_i16(v),
+            Nbt::Int(v) => visitor.visit_i32(v),
+            Nbt::Long(v) => visitor.visit_i64(v),
+            Nbt::Float(v) => visitor.visit_f32(v),
+            Nbt::Double(v) => visitor.visit_f64(v),
+            Nbt::String(v) => visitor.visit_string(v),
+            Nbt::List(list) => visitor.visit_seq(ListAccess(list.into_iter())),
+            Nbt::Compound(compound) => {
+                visitor.visit_map(CompoundAccess(compound.into_iter(), None))
+            }
+            array => visitor.visit_enum(ArrayAccess(array)),
+        }
+    }
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Nbt::Byte(v) => visitor.visit_bool(v != 0),
+            nbt => nbt.deserialize_any(visitor),
+        }
+    }
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let list: Vec<Nbt> = match self {
+            Nbt::ByteArray(array) => array.into_iter().map(Nbt::Byte).collect(),
+            Nbt::IntArray(array) => array.into_iter().map(Nbt::Int).collect(),
+            Nbt::LongArray(array) => array.into_iter().map(Nbt::Long).collect(),
+            nbt => return nbt.deserialize_any(visitor),
+        };
+
+        visitor.visit_seq(ListAccess(list.into_iter()))
+    }
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self {
+            Nbt::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Nbt::Compound(compound) if compound.len() == 1 => {
+                let (variant, value) = compound.into_iter().next().unwrap();
+                visitor.visit_enum(VariantValue(variant, value))
+            }
+            _ => Err(Error(
+                "Expected a string or a compound with one entry for an enum".to_string(),
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct map struct identifier ignored_any
+    }
+}
+
+impl IntoDeserializer<'_, Error> for Nbt {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+struct ListAccess(std::vec::IntoIter<Nbt>);
+
+impl<'de> SeqAccess<'de> for ListAccess {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        self.0.next().map(|nbt| seed.deserialize(nbt)).transpose()
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+/// The value waiting to be read after its key
+struct CompoundAccess(indexmap::map::IntoIter<String, Nbt>, Option<Nbt>);
+
+impl<'de> MapAccess<'de> for CompoundAccess {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.0.next() {
+            Some((key, value)) => {
+                self.1 = Some(value);
+                let key: StringDeserializer<Error> = key.into_deserializer();
+
+                seed.deserialize(key).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value = self
+            .1
+            .take()
+            .ok_or_else(|| Error("Value without a key".to_string()))?;
+
+        seed.deserialize(value)
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+/// An enum variant with fields, from a compound with the variant name as the only key
+struct VariantValue(String, Nbt);
+
+impl<'de> EnumAccess<'de> for VariantValue {
+    type Error = Error;
+    type Variant = Nbt;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Nbt), Error> {
+        let variant: StringDeserializer<Error> = self.0.into_deserializer();
+
+        Ok((seed.deserialize(variant)?, self.1))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Nbt {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_any(visitor)
+    }
+}
+
+/// Arrays are passed to [`Nbt`]'s visitor as an enum variant named after the array type,
+/// as lists and arrays would be the same sequences otherwise
+struct ArrayAccess(Nbt);
+
+impl<'de> EnumAccess<'de> for ArrayAccess {
+    type Error = Error;
+    type Variant = Nbt;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Nbt), Error> {
+        let name = match self.0 {
+            Nbt::ByteArray(_) => BYTE_ARRAY,
+            Nbt::IntArray(_) => INT_ARRAY,
+            _ => LONG_ARRAY,
+        };
+        let name: StringDeserializer<Error> = name.to_string().into_deserializer();
+
+        Ok((seed.deserialize(name)?, self.0))
+    }
+}
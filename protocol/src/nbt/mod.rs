@@ -0,0 +1,563 @@
+//! NBT (Named Binary Tag), the binary format used for items, chunks, registries and files
+//!
+//! [`Nbt`] is a value of any tag. On the wire it's either a [`NetworkNbt`], without a
+//! name for the root tag, or a [`NamedNbt`], which files use and which can be gzip or
+//! zlib compressed.
+//!
+//! Rust types can be converted with [`to_nbt`] and [`from_nbt`] using serde, and
+//! [`Nbt`] can be printed as and parsed from SNBT, the text format used in commands.
+
+mod de;
+mod ser;
+mod snbt;
+
+pub use de::from_nbt;
+pub use ser::{to_nbt, ByteArray, IntArray, LongArray};
+
+use crate::{limits, FromBytes, ToBytes};
+use flate2::{
+    read::{GzDecoder, ZlibDecoder},
+    write::{GzEncoder, ZlibEncoder},
+};
+use indexmap::IndexMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    fmt,
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+};
+
+pub type Compound = IndexMap<String, Nbt>;
+
+/// Maximum nesting of compounds and lists, like in vanilla
+const MAX_DEPTH: usize = 512;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Nbt {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    /// All elements must be of the same type
+    List(Vec<Nbt>),
+    Compound(Compound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+/// NBT with a nameless root tag, as sent over the network since 1.20.2
+///
+/// `None` is an empty tag (`TAG_End`), used for missing NBT such as items without any.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NetworkNbt(pub Option<Nbt>);
+
+/// NBT with a named root tag, as in files and sent over the network before 1.20.2
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedNbt {
+    /// Usually empty
+    pub name: String,
+    pub value: Nbt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileCompression {
+    None,
+    /// Used by most files, such as `level.dat`
+    #[default]
+    Gzip,
+    /// Used for chunks in region files
+    Zlib,
+}
+
+/// An error converting to or from NBT
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error(String);
+
+impl Nbt {
+    /// The tag id
+    pub fn id(&self) -> u8 {
+        match self {
+            Nbt::Byte(_) => 1,
+            Nbt::Short(_) => 2,
+            Nbt::Int(_) => 3,
+            Nbt::Long(_) => 4,
+            Nbt::Float(_) => 5,
+            Nbt::Double(_) => 6,
+            Nbt::ByteArray(_) => 7,
+            Nbt::String(_) => 8,
+            Nbt::List(_) => 9,
+            Nbt::Compound(_) => 10,
+            Nbt::IntArray(_) => 11,
+            Nbt::LongArray(_) => 12,
+        }
+    }
+    pub fn as_compound(&self) -> Option<&Compound> {
+        match self {
+            Nbt::Compound(compound) => Some(compound),
+            _ => None,
+        }
+    }
+    /// Gets a value in a compound
+    pub fn get(&self, key: &str) -> Option<&Nbt> {
+        self.as_compound()?.get(key)
+    }
+
+    fn read_payload<R: Read>(read: &mut R, id: u8, depth: usize) -> io::Result<Self> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("NBT nested too deep"));
+        }
+
+        Ok(match id {
+            1 => Nbt::Byte(i8::read_from(read)?),
+            2 => Nbt::Short(i16::read_from(read)?),
+            3 => Nbt::Int(i32::read_from(read)?),
+            4 => Nbt::Long(i64::read_from(read)?),
+            5 => Nbt::Float(f32::read_from(read)?),
+            6 => Nbt::Double(f64::read_from(read)?),
+            7 => Nbt::ByteArray(read_array(read)?),
+            8 => Nbt::String(read_string(read)?),
+            9 => {
+                let element_id = u8::read_from(read)?;
+                let length = read_length::<Nbt, _>(read)?;
+
+                if element_id == 0 && length > 0 {
+                    return Err(invalid("NBT list of end tags"));
+                }
+
+                let mut list = Vec::with_capacity(length);
+                for _ in 0..length {
+                    list.push(Self::read_payload(read, element_id, depth + 1)?);
+                }
+
+                Nbt::List(list)
+            }
+            10 => {
+                let mut compound = Compound::new();
+
+                loop {
+                    let id = u8::read_from(read)?;
+                    if id == 0 {
+                        break;
+                    }
+
+                    let name = read_string(read)?;
+                    limits::allocate::<(String, Nbt)>(1)?;
+                    compound.insert(name, Self::read_payload(read, id, depth + 1)?);
+                }
+
+                Nbt::Compound(compound)
+            }
+            11 => Nbt::IntArray(read_array(read)?),
+            12 => Nbt::LongArray(read_array(read)?),
+            id => return Err(invalid(&format!("Invalid NBT tag id {id}"))),
+        })
+    }
+    fn write_payload<W: Write>(&self, write: &mut W) -> io::Result<usize> {
+        Ok(match self {
+            Nbt::Byte(v) => v.write_to(write)?,
+            Nbt::Short(v) => v.write_to(write)?,
+            Nbt::Int(v) => v.write_to(write)?,
+            Nbt::Long(v) => v.write_to(write)?,
+            Nbt::Float(v) => v.write_to(write)?,
+            Nbt::Double(v) => v.write_to(write)?,
+            Nbt::ByteArray(array) => write_array(array, write)?,
+            Nbt::String(string) => write_string(string, write)?,
+            Nbt::List(list) => {
+                let element_id = list.first().map_or(0, Nbt::id);
+                if list.iter().any(|e| e.id() != element_id) {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidInput,
+                        "NBT list elements must be of the same type",
+                    ));
+                }
+
+                let mut written = element_id.write_to(write)?;
+                written += (list.len() as i32).write_to(write)?;
+                for element in list {
+                    written += element.write_payload(write)?;
+                }
+
+                written
+            }
+            Nbt::Compound(compound) => {
+                let mut written = 0;
+
+                for (name, value) in compound {
+                    written += value.id().write_to(write)?;
+                    written += write_string(name, write)?;
+                    written += value.write_payload(write)?;
+                }
+
+                written + 0u8.write_to(write)?
+            }
+            Nbt::IntArray(array) => write_array(array, write)?,
+            Nbt::LongArray(array) => write_array(array, write)?,
+        })
+    }
+    fn payload_size(&self) -> usize {
+        match self {
+            Nbt::Byte(_) => 1,
+            Nbt::Short(_) => 2,
+            Nbt::Int(_) | Nbt::Float(_) => 4,
+            Nbt::Long(_) | Nbt::Double(_) => 8,
+            Nbt::ByteArray(array) => 4 + array.len(),
+            Nbt::String(string) => string_size(string),
+            Nbt::List(list) => 5 + list.iter().map(Nbt::payload_size).sum::<usize>(),
+            Nbt::Compound(compound) => {
+                let entries: usize = compound
+                    .iter()
+                    .map(|(name, value)| 1 + string_size(name) + value.payload_size())
+                    .sum();
+
+                entries + 1
+            }
+            Nbt::IntArray(array) => 4 + array.len() * 4,
+            Nbt::LongArray(array) => 4 + array.len() * 8,
+        }
+    }
+}
+
+impl From<Compound> for Nbt {
+    fn from(compound: Compound) -> Self {
+        Nbt::Compound(compound)
+    }
+}
+
+impl FromBytes for NetworkNbt {
+    fn read_from<R: Read>(read: &mut R) -> io::Result<Self> {
+        match u8::read_from(read)? {
+            0 => Ok(Self(None)),
+            id => Ok(Self(Some(Nbt::read_payload(read, id, 0)?))),
+        }
+    }
+}
+
+impl ToBytes for NetworkNbt {
+    fn write_to<W: Write>(&self, write: &mut W) -> io::Result<usize> {
+        match &self.0 {
+            Some(nbt) => Ok(nbt.id().write_to(write)? + nbt.write_payload(write)?),
+            None => 0u8.write_to(write),
+        }
+    }
+    fn encoded_size(&self) -> usize {
+        1 + self.0.as_ref().map_or(0, Nbt::payload_size)
+    }
+}
+
+impl FromBytes for NamedNbt {
+    fn read_from<R: Read>(read: &mut R) -> io::Result<Self> {
+        let id = u8::read_from(read)?;
+        if id == 0 {
+            return Err(invalid("Named NBT can't be empty"));
+        }
+
+        let name = read_string(read)?;
+        let value = Nbt::read_payload(read, id, 0)?;
+
+        Ok(Self { name, value })
+    }
+}
+
+impl ToBytes for NamedNbt {
+    fn write_to<W: Write>(&self, write: &mut W) -> io::Result<usize> {
+        let written = self.value.id().write_to(write)? + write_string(&self.name, write)?;
+
+        Ok(written + self.value.write_payload(write)?)
+    }
+    fn encoded_size(&self) -> usize {
+        1 + string_size(&self.name) + self.value.payload_size()
+    }
+}
+
+impl NamedNbt {
+    /// Reads an NBT file, detecting its compression
+    pub fn read_file<R: Read>(read: R) -> io::Result<Self> {
+        let mut read = BufReader::new(read);
+
+        match read.fill_buf()? {
+            [0x1F, 0x8B, ..] => Self::read_from(&mut GzDecoder::new(read)),
+            [0x78, ..] => Self::read_from(&mut ZlibDecoder::new(read)),
+            _ => Self::read_from(&mut read),
+        }
+    }
+    pub fn write_file<W: Write>(&self, write: W, compression: FileCompression) -> io::Result<()> {
+        let level = flate2::Compression::default();
+
+        match compression {
+            FileCompression::None => {
+                let mut writer = io::BufWriter::new(write);
+                self.write_to(&mut writer)?;
+                writer.flush()?;
+            }
+            FileCompression::Gzip => {
+                let mut encoder = GzEncoder::new(write, level);
+                self.write_to(&mut encoder)?;
+                encoder.finish()?.flush()?;
+            }
+            FileCompression::Zlib => {
+                let mut encoder = ZlibEncoder::new(write, level);
+                self.write_to(&mut encoder)?;
+                encoder.finish()?.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Serialize for NetworkNbt {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for NetworkNbt {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Option::deserialize(deserializer).map(Self)
+    }
+}
+
+/// Array types, which are read and written with a length prefix
+trait ArrayElement: FromBytes + ToBytes + Sized {}
+
+impl ArrayElement for i8 {}
+impl ArrayElement for i32 {}
+impl ArrayElement for i64 {}
+
+fn read_array<T: ArrayElement, R: Read>(read: &mut R) -> io::Result<Vec<T>> {
+    let length = read_length::<T, _>(read)?;
+
+    let mut array = Vec::with_capacity(length);
+    for _ in 0..length {
+        array.push(T::read_from(read)?);
+    }
+
+    Ok(array)
+}
+
+fn write_array<T: ArrayElement, W: Write>(array: &[T], write: &mut W) -> io::Result<usize> {
+    let length = i32::try_from(array.len()).map_err(|_| invalid("NBT array too long"))?;
+
+    let mut written = length.write_to(write)?;
+    for element in array {
+        written += element.write_to(write)?;
+    }
+
+    Ok(written)
+}
+
+/// Reads an `i32` length, and checks it against the limits for elements of `T`
+fn read_length<T, R: Read>(read: &mut R) -> io::Result<usize> {
+    let length = i32::read_from(read)?;
+
+    if length < 0 {
+        return Err(invalid("Negative NBT length"));
+    }
+    limits::check_length(length as usize)?;
+    limits::allocate::<T>(length as usize)?;
+
+    Ok(length as usize)
+}
+
+/// Reads a string in Java's modified UTF-8, which encodes NUL as 2 bytes and characters
+/// outside the BMP as surrogate pairs of 3 bytes each
+fn read_string<R: Read>(read: &mut R) -> io::Result<String> {
+    let length = u16::read_from(read)? as usize;
+    limits::allocate::<u8>(length)?;
+
+    let mut bytes = vec![0; length];
+    read.read_exact(&mut bytes)?;
+
+    // Most strings are plain ASCII, which is the same in both
+    if !bytes.iter().any(|b| *b == 0 || *b >= 0x80) {
+        return Ok(String::from_utf8(bytes).unwrap());
+    }
+
+    let malformed = || invalid("NBT string not valid modified UTF-8");
+    let mut units = Vec::with_capacity(length);
+    let mut bytes = bytes.into_iter();
+
+    while let Some(first) = bytes.next() {
+        let mut continuation = || match bytes.next() {
+            Some(b) if b & 0xC0 == 0x80 => Ok((b & 0x3F) as u16),
+            _ => Err(malformed()),
+        };
+
+        units.push(match first {
+            0x01..=0x7F => first as u16,
+            0xC0..=0xDF => ((first & 0x1F) as u16) << 6 | continuation()?,
+            0xE0..=0xEF => ((first & 0x0F) as u16) << 12 | continuation()? << 6 | continuation()?,
+            _ => return Err(malformed()),
+        });
+    }
+
+    String::from_utf16(&units).map_err(|_| malformed())
+}
+
+fn write_string<W: Write>(string: &str, write: &mut W) -> io::Result<usize> {
+    let length =
+        u16::try_from(string_size(string) - 2).map_err(|_| invalid("NBT string too long"))?;
+    let mut written = length.write_to(write)?;
+
+    if !string.bytes().any(|b| b == 0 || b >= 0x80) {
+        write.write_all(string.as_bytes())?;
+        return Ok(written + string.len());
+    }
+
+    let mut bytes = Vec::with_capacity(length as usize);
+    for unit in string.encode_utf16() {
+        match unit {
+            0x01..=0x7F => bytes.push(unit as u8),
+            0x00 | 0x80..=0x7FF => {
+                bytes.push(0xC0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                bytes.push(0xE0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+    write.write_all(&bytes)?;
+    written += bytes.len();
+
+    Ok(written)
+}
+
+/// Size of a string with its length prefix
+fn string_size(string: &str) -> usize {
+    let encoded: usize = string
+        .encode_utf16()
+        .map(|unit| match unit {
+            0x01..=0x7F => 1,
+            0x00 | 0x80..=0x7FF => 2,
+            _ => 3,
+        })
+        .sum();
+
+    2 + encoded
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl serde::ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+impl serde::de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Player {
+        name: String,
+        health: f32,
+        on_ground: bool,
+        inventory: Vec<Item>,
+        seeds: LongArray,
+        spawn: Option<i32>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        id: String,
+        count: i8,
+    }
+
+    /// Accepts only `capacity` bytes, like a full disk
+    struct Full {
+        capacity: usize,
+    }
+
+    impl Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            match self.capacity.min(buf.len()) {
+                0 => Err(io::ErrorKind::WriteZero.into()),
+                n => {
+                    self.capacity -= n;
+                    Ok(n)
+                }
+            }
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn binary_and_serde_round_trip() {
+        let player = Player {
+            name: "Steve\0🦀".to_string(),
+            health: 20.0,
+            on_ground: true,
+            inventory: vec![Item {
+                id: "minecraft:stone".to_string(),
+                count: 64,
+            }],
+            seeds: LongArray(vec![1, -1]),
+            spawn: None,
+        };
+
+        let nbt = to_nbt(&player).unwrap();
+        assert_eq!(nbt.get("seeds"), Some(&Nbt::LongArray(vec![1, -1])));
+        assert_eq!(nbt.get("spawn"), None);
+
+        let network = NetworkNbt(Some(nbt.clone()));
+        let mut bytes = Vec::new();
+        assert_eq!(
+            network.write_to(&mut bytes).unwrap(),
+            network.encoded_size()
+        );
+        // NUL and each half of the surrogate pair take more bytes than in UTF-8
+        assert_eq!(&bytes[8..21], b"\x00\x0DSteve\xC0\x80\xED\xA0\xBE\xED");
+        assert_eq!(
+            NetworkNbt::read_from(&mut bytes.as_slice()).unwrap(),
+            network
+        );
+
+        let named = NamedNbt {
+            name: String::new(),
+            value: nbt,
+        };
+        for compression in [
+            FileCompression::None,
+            FileCompression::Gzip,
+            FileCompression::Zlib,
+        ] {
+            let mut file = Vec::new();
+            named.write_file(&mut file, compression).unwrap();
+            assert_eq!(NamedNbt::read_file(file.as_slice()).unwrap(), named);
+
+            // Errors are returned even if they only happen when flushing
+            let full = Full {
+                capacity: file.len() - 1,
+            };
+            assert!(named.write_file(full, compression).is_err());
+        }
+
+        assert_eq!(from_nbt::<Player>(named.value).unwrap(), player);
+        assert!(NetworkNbt::read_from(&mut [9, 0, 0xFF, 0xFF, 0xFF, 0xFF].as_slice()).is_err());
+    }
+}
@@ -0,0 +1,533 @@
+//! Converting Rust types to NBT with serde
+
+use super::{Compound, Error, Nbt};
+use serde::{
+    ser::{self, Impossible},
+    Deserialize, Deserializer, Serialize,
+};
+
+/// Newtype struct names that mark arrays, which would otherwise be lists
+pub(super) const BYTE_ARRAY: &str = "__nbt_byte_array";
+pub(super) const INT_ARRAY: &str = "__nbt_int_array";
+pub(super) const LONG_ARRAY: &str = "__nbt_long_array";
+
+/// Returned for `None`, so that compounds can leave the field out
+const NONE: &str = "__nbt_none";
+
+/// Converts a value to NBT
+///
+/// Structs and maps become compounds and sequences become lists, use [`ByteArray`],
+/// [`IntArray`] and [`LongArray`] for arrays. `None` fields are left out, `bool`s are
+/// bytes and unsigned integers become the next larger signed type.
+pub fn to_nbt<T: Serialize + ?Sized>(value: &T) -> Result<Nbt, Error> {
+    value.serialize(Serializer).map_err(|e| match e.0.as_str() {
+        NONE => Error("None can only be a field of a compound".to_string()),
+        _ => e,
+    })
+}
+
+/// Serializes as `TAG_Byte_Array` instead of a list
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ByteArray(pub Vec<i8>);
+
+/// Serializes as `TAG_Int_Array` instead of a list
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct IntArray(pub Vec<i32>);
+
+/// Serializes as `TAG_Long_Array` instead of a list
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LongArray(pub Vec<i64>);
+
+macro_rules! impl_array_serde {
+    ( $( $type:ident, $name:ident );+ ) => {
+        $(
+            impl Serialize for $type {
+                fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.serialize_newtype_struct($name, &self.0)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $type {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    Deserialize::deserialize(deserializer).map(Self)
+                }
+            }
+        )+
+    };
+}
+
+impl_array_serde! { ByteArray, BYTE_ARRAY; IntArray, INT_ARRAY; LongArray, LONG_ARRAY }
+
+impl Serialize for Nbt {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Nbt::Byte(v) => serializer.serialize_i8(*v),
+            Nbt::Short(v) => serializer.serialize_i16(*v),
+            Nbt::Int(v) => serializer.serialize_i32(*v),
+            Nbt::Long(v) => serializer.serialize_i64(*v),
+            Nbt::Float(v) => serializer.serialize_f32(*v),
+            Nbt::Double(v) => serializer.serialize_f64(*v),
+            Nbt::ByteArray(array) => serializer.serialize_newtype_struct(BYTE_ARRAY, array),
+            Nbt::String(string) => serializer.serialize_str(string),
+            Nbt::List(list) => serializer.collect_seq(list),
+            Nbt::Compound(compound) => serializer.collect_map(compound),
+            Nbt::IntArray(array) => serializer.serialize_newtype_struct(INT_ARRAY, array),
+            Nbt::LongArray(array) => serializer.serialize_newtype_struct(LONG_ARRAY, array),
+        }
+    }
+}
+
+struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Nbt;
+    type Error = Error;
+
+    type SerializeSeq = ListSerializer;
+    type SerializeTuple = ListSerializer;
+    type SerializeTupleStruct = ListSerializer;
+    type SerializeTupleVariant = VariantSerializer<ListSerializer>;
+    type SerializeMap = CompoundSerializer;
+    type SerializeStruct = CompoundSerializer;
+    type SerializeStructVariant = VariantSerializer<CompoundSerializer>;
+
+    fn serialize_bool(self, v: bool) -> Result<Nbt, Error> {
+        Ok(Nbt::Byte(v as i8))
+    }
+    fn serialize_i8(self, v: i8) -> Result<Nbt, Error> {
+        Ok(Nbt::Byte(v))
+    }
+    fn serialize_i16(self, v: i16) -> Result<Nbt, Error> {
+        Ok(Nbt::Short(v))
+    }
+    fn serialize_i32(self, v: i32) -> Result<Nbt, Error> {
+        Ok(Nbt::Int(v))
+    }
+    fn serialize_i64(self, v: i64) -> Result<Nbt, Error> {
+        Ok(Nbt::Long(v))
+    }
+    fn serialize_u8(self, v: u8) -> Result<Nbt, Error> {
+        Ok(Nbt::Short(v.into()))
+    }
+    fn serialize_u16(self, v: u16) -> Result<Nbt, Error> {
+        Ok(Nbt::Int(v.into()))
+    }
+    fn serialize_u32(self, v: u32) -> Result<Nbt, Error> {
+        Ok(Nbt::Long(v.into()))
+    }
+    fn serialize_u64(self, v: u64) -> Result<Nbt, Error> {
+        i64::try_from(v)
+            .map(Nbt::Long)
+            .map_err(|_| Error(format!("{v} is too large for NBT")))
+    }
+    fn serialize_f32(self, v: f32) -> Result<Nbt, Error> {
+        Ok(Nbt::Float(v))
+    }
+    fn serialize_f64(self, v: f64) -> Result<Nbt, Error> {
+        Ok(Nbt::Double(v))
+    }
+    fn serialize_char(self, v: char) -> Result<Nbt, Error> {
+        Ok(Nbt::String(v.to_string()))
+    }
+    fn serialize_str(self, v: &str) -> Result<Nbt, Error> {
+        Ok(Nbt::String(v.to_string()))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Nbt, Error> {
+        Ok(Nbt::ByteArray(v.iter().map(|b| *b as i8).collect()))
+    }
+    fn serialize_none(self) -> Result<Nbt, Error> {
+        Err(Error(NONE.to_string()))
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Nbt, Error> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<Nbt, Error> {
+        Ok(Nbt::Compound(Compound::new()))
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Nbt, Error> {
+        self.serialize_unit()
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Nbt, Error> {
+        Ok(Nbt::String(variant.to_string()))
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Nbt, Error> {
+        let nbt = value.serialize(self)?;
+        let elements = match (name, &nbt) {
+            (BYTE_ARRAY | INT_ARRAY | LONG_ARRAY, Nbt::List(elements)) => elements,
+            _ => return Ok(nbt),
+        };
+
+        let array = match name {
+            BYTE_ARRAY => Nbt::ByteArray(array_elements(elements, |e| match e {
+                Nbt::Byte(v) => Some(*v),
+                _ => None,
+            })?),
+            INT_ARRAY => Nbt::IntArray(array_elements(elements, |e| match e {
+                Nbt::Int(v) => Some(*v),
+                _ => None,
+            })?),
+            _ => Nbt::LongArray(array_elements(elements, |e| match e {
+                Nbt::Long(v) => Some(*v),
+                _ => None,
+            })?),
+        };
+
+        Ok(array)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Nbt, Error> {
+        let mut compound = Compound::new();
+        compound.insert(variant.to_string(), to_nbt(value)?);
+
+        Ok(Nbt::Compound(compound))
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<ListSerializer, Error> {
+        Ok(ListSerializer(Vec::with_capacity(len.unwrap_or(0))))
+    }
+    fn serialize_tuple(self, len: usize) -> Result<ListSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<ListSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<VariantSerializer<ListSerializer>, Error> {
+        Ok(VariantSerializer(variant, self.serialize_seq(Some(len))?))
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<CompoundSerializer, Error> {
+        Ok(CompoundSerializer(Compound::new(), None))
+    }
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<CompoundSerializer, Error> {
+        self.serialize_map(Some(len))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<VariantSerializer<CompoundSerializer>, Error> {
+        Ok(VariantSerializer(variant, self.serialize_map(Some(len))?))
+    }
+}
+
+fn array_elements<T>(elements: &[Nbt], f: impl Fn(&Nbt) -> Option<T>) -> Result<Vec<T>, Error> {
+    elements
+        .iter()
+        .map(|e| f(e).ok_or_else(|| Error("NBT array elements of the wrong type".to_string())))
+        .collect()
+}
+
+struct ListSerializer(Vec<Nbt>);
+
+impl ListSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let element = to_nbt(value)?;
+
+        if self
+            .0
+            .first()
+            .is_some_and(|first| first.id() != element.id())
+        {
+            return Err(Error(
+                "NBT list elements must be of the same type".to_string(),
+            ));
+        }
+
+        self.0.push(element);
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for ListSerializer {
+    type Ok = Nbt;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Nbt, Error> {
+        Ok(Nbt::List(self.0))
+    }
+}
+
+impl ser::SerializeTuple for ListSerializer {
+    type Ok = Nbt;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Nbt, Error> {
+        Ok(Nbt::List(self.0))
+    }
+}
+
+impl ser::SerializeTupleStruct for ListSerializer {
+    type Ok = Nbt;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Nbt, Error> {
+        Ok(Nbt::List(self.0))
+    }
+}
+
+/// The key waiting for its value
+struct CompoundSerializer(Compound, Option<String>);
+
+impl CompoundSerializer {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), Error> {
+        match value.serialize(Serializer) {
+            Ok(value) => {
+                self.0.insert(key, value);
+                Ok(())
+            }
+            Err(e) if e.0 == NONE => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl ser::SerializeMap for CompoundSerializer {
+    type Ok = Nbt;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.1 = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .1
+            .take()
+            .ok_or_else(|| Error("Value without a key".to_string()))?;
+
+        self.insert(key, value)
+    }
+    fn end(self) -> Result<Nbt, Error> {
+        Ok(Nbt::Compound(self.0))
+    }
+}
+
+impl ser::SerializeStruct for CompoundSerializer {
+    type Ok = Nbt;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(key.to_string(), value)
+    }
+    fn end(self) -> Result<Nbt, Error> {
+        Ok(Nbt::Compound(self.0))
+    }
+}
+
+/// Wraps the variant's fields in a compound with the variant name as the only key
+struct VariantSerializer<S>(&'static str, S);
+
+impl<S> VariantSerializer<S> {
+    fn wrap(variant: &'static str, value: Nbt) -> Nbt {
+        let mut compound = Compound::new();
+        compound.insert(variant.to_string(), value);
+
+        Nbt::Compound(compound)
+    }
+}
+
+impl ser::SerializeTupleVariant for VariantSerializer<ListSerializer> {
+    type Ok = Nbt;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.1.push(value)
+    }
+    fn end(self) -> Result<Nbt, Error> {
+        Ok(Self::wrap(self.0, Nbt::List(self.1 .0)))
+    }
+}
+
+impl ser::SerializeStructVariant for VariantSerializer<CompoundSerializer> {
+    type Ok = Nbt;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.1.insert(key.to_string(), value)
+    }
+    fn end(self) -> Result<Nbt, Error> {
+        Ok(Self::wrap(self.0, Nbt::Compound(self.1 .0)))
+    }
+}
+
+/// Compound keys can only be strings
+struct KeySerializer;
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Error;
+
+    type SerializeSeq = Impossible<String, Error>;
+    type SerializeTuple = Impossible<String, Error>;
+    type SerializeTupleStruct = Impossible<String, Error>;
+    type SerializeTupleVariant = Impossible<String, Error>;
+    type SerializeMap = Impossible<String, Error>;
+    type SerializeStruct = Impossible<String, Error>;
+    type SerializeStructVariant = Impossible<String, Error>;
+
+    fn serialize_str(self, v: &str) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+    fn serialize_char(self, v: char) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, Error> {
+        Ok(variant.to_string())
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _: bool) -> Result<String, Error> {
+        Err(key_error())
+    }
+    fn serialize_i8(self, _: i8) -> Result<String, Error> {
+        Err(key_error())
+    }
+    fn serialize_i16(self, _: i16) -> Result<String, Error> {
+        Err(key_error())
+    }
+    fn serialize_i32(self, _: i32) -> Result<String, Error> {
+        Err(key_error())
+    }
+    fn serialize_i64(self, _: i64) -> Result<String, Error> {
+        Err(key_error())
+    }
+    fn serialize_u8(self, _: u8) -> Result<String, Error> {
+        Err(key_error())
+    }
+    fn serialize_u16(self, _: u16) -> Result<String, Error> {
+        Err(key_error())
+    }
+    fn serialize_u32(self, _: u32) -> Result<String, Error> {
+        Err(key_error())
+    }
+    fn serialize_u64(self, _: u64) -> Result<String, Error> {
+        Err(key_error())
+    }
+    fn serialize_f32(self, _: f32) -> Result<String, Error> {
+        Err(key_error())
+    }
+    fn serialize_f64(self, _: f64) -> Result<String, Error> {
+        Err(key_error())
+    }
+    fn serialize_bytes(self, _: &[u8]) -> Result<String, Error> {
+        Err(key_error())
+    }
+    fn serialize_none(self) -> Result<String, Error> {
+        Err(key_error())
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, _: &T) -> Result<String, Error> {
+        Err(key_error())
+    }
+    fn serialize_unit(self) -> Result<String, Error> {
+        Err(key_error())
+    }
+    fn serialize_unit_struct(self, _: &'static str) -> Result<String, Error> {
+        Err(key_error())
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<String, Error> {
+        Err(key_error())
+    }
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(key_error())
+    }
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(key_error())
+    }
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(key_error())
+    }
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(key_error())
+    }
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(key_error())
+    }
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, Error> {
+        Err(key_error())
+    }
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(key_error())
+    }
+}
+
+fn key_error() -> Error {
+    Error("NBT compound keys must be strings".to_string())
+}
@@ -0,0 +1,364 @@
+//! SNBT (stringified NBT), the text format used in commands
+
+use super::{Compound, Error, Nbt, MAX_DEPTH};
+use std::{
+    fmt::{self, Display, Formatter, Write},
+    str::FromStr,
+};
+
+impl Display for Nbt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Nbt::Byte(v) => write!(f, "{v}b"),
+            Nbt::Short(v) => write!(f, "{v}s"),
+            Nbt::Int(v) => write!(f, "{v}"),
+            Nbt::Long(v) => write!(f, "{v}L"),
+            Nbt::Float(v) => write!(f, "{v}f"),
+            Nbt::Double(v) => write!(f, "{v}d"),
+            Nbt::ByteArray(array) => write_array(f, "B", array.iter().map(|v| format!("{v}b"))),
+            Nbt::String(string) => write_quoted(f, string),
+            Nbt::List(list) => {
+                f.write_char('[')?;
+                for (i, element) in list.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{element}")?;
+                }
+                f.write_char(']')
+            }
+            Nbt::Compound(compound) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in compound.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    if !key.is_empty() && key.chars().all(is_bare) {
+                        f.write_str(key)?;
+                    } else {
+                        write_quoted(f, key)?;
+                    }
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+            Nbt::IntArray(array) => write_array(f, "I", array.iter().map(|v| v.to_string())),
+            Nbt::LongArray(array) => write_array(f, "L", array.iter().map(|v| format!("{v}L"))),
+        }
+    }
+}
+
+fn write_array(
+    f: &mut Formatter<'_>,
+    prefix: &str,
+    elements: impl Iterator<Item = String>,
+) -> fmt::Result {
+    write!(f, "[{prefix};")?;
+    for (i, element) in elements.enumerate() {
+        if i > 0 {
+            f.write_char(',')?;
+        }
+        f.write_str(&element)?;
+    }
+    f.write_char(']')
+}
+
+fn write_quoted(f: &mut Formatter<'_>, string: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in string.chars() {
+        if c == '"' || c == '\\' {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    f.write_char('"')
+}
+
+/// Characters allowed in unquoted strings and keys
+fn is_bare(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '+' | '-')
+}
+
+impl FromStr for Nbt {
+    type Err = Error;
+
+    /// Parses SNBT. Numbers without a suffix are ints, or doubles if they have a decimal
+    /// point or exponent, and `true`/`false` are bytes.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut parser = Parser { input: s, pos: 0 };
+
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+        if parser.pos < s.len() {
+            return Err(parser.error("Trailing characters after SNBT"));
+        }
+
+        Ok(value)
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    /// Byte offset into the input
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn value(&mut self, depth: usize) -> Result<Nbt, Error> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("SNBT nested too deep"));
+        }
+
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.compound(depth),
+            Some('[') => self.list(depth),
+            Some('"' | '\'') => self.quoted().map(Nbt::String),
+            _ => {
+                let token = self.bare()?;
+                Ok(parse_bare(token))
+            }
+        }
+    }
+
+    fn compound(&mut self, depth: usize) -> Result<Nbt, Error> {
+        self.expect('{')?;
+        let mut compound = Compound::new();
+
+        self.skip_whitespace();
+        if self.eat('}') {
+            return Ok(Nbt::Compound(compound));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = match self.peek() {
+                Some('"' | '\'') => self.quoted()?,
+                _ => self.bare()?.to_string(),
+            };
+
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.value(depth + 1)?;
+            compound.insert(key, value);
+
+            if !self.separator('}')? {
+                return Ok(Nbt::Compound(compound));
+            }
+        }
+    }
+
+    fn list(&mut self, depth: usize) -> Result<Nbt, Error> {
+        self.expect('[')?;
+
+        let rest = &self.input[self.pos..];
+        for prefix in ["B;", "I;", "L;"] {
+            if rest.starts_with(prefix) {
+                self.pos += prefix.len();
+                return self.array(&prefix[..1]);
+            }
+        }
+
+        let mut list: Vec<Nbt> = Vec::new();
+        self.skip_whitespace();
+        if self.eat(']') {
+            return Ok(Nbt::List(list));
+        }
+
+        loop {
+            let start = self.pos;
+            let element = self.value(depth + 1)?;
+
+            if list.first().is_some_and(|first| first.id() != element.id()) {
+                self.pos = start;
+                return Err(self.error("SNBT list elements must be of the same type"));
+            }
+            list.push(element);
+
+            if !self.separator(']')? {
+                return Ok(Nbt::List(list));
+            }
+        }
+    }
+
+    fn array(&mut self, kind: &str) -> Result<Nbt, Error> {
+        let mut elements = Vec::new();
+
+        self.skip_whitespace();
+        if !self.eat(']') {
+            loop {
+                self.skip_whitespace();
+                let start = self.pos;
+                elements.push((start, parse_bare(self.bare()?)));
+
+                if !self.separator(']')? {
+                    break;
+                }
+            }
+        }
+
+        macro_rules! collect {
+            ( $variant:ident, $element:ident ) => {
+                elements
+                    .into_iter()
+                    .map(|(start, element)| match element {
+                        Nbt::$element(v) => Ok(v),
+                        _ => {
+                            self.pos = start;
+                            Err(self.error("SNBT array element of the wrong type"))
+                        }
+                    })
+                    .collect::<Result<_, _>>()
+                    .map(Nbt::$variant)
+            };
+        }
+
+        match kind {
+            "B" => collect!(ByteArray, Byte),
+            "I" => collect!(IntArray, Int),
+            _ => collect!(LongArray, Long),
+        }
+    }
+
+    /// Consumes a `,` and returns true, or consumes the closing character and returns false
+    fn separator(&mut self, close: char) -> Result<bool, Error> {
+        self.skip_whitespace();
+
+        if self.eat(',') {
+            Ok(true)
+        } else if self.eat(close) {
+            Ok(false)
+        } else {
+            Err(self.error(&format!("Expected ',' or '{close}'")))
+        }
+    }
+
+    fn quoted(&mut self) -> Result<String, Error> {
+        let quote = self.next().unwrap();
+        let mut string = String::new();
+
+        loop {
+            match self.next() {
+                Some('\\') => match self.next() {
+                    Some(c @ ('\\' | '"' | '\'')) => string.push(c),
+                    _ => return Err(self.error("Invalid escape in SNBT string")),
+                },
+                Some(c) if c == quote => return Ok(string),
+                Some(c) => string.push(c),
+                None => return Err(self.error("Unterminated SNBT string")),
+            }
+        }
+    }
+
+    fn bare(&mut self) -> Result<&str, Error> {
+        let start = self.pos;
+        while self.peek().is_some_and(is_bare) {
+            self.pos += 1;
+        }
+
+        if start == self.pos {
+            return Err(self.error("Expected an SNBT value"));
+        }
+
+        Ok(&self.input[start..self.pos])
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.next();
+        }
+    }
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+
+        Some(c)
+    }
+    fn eat(&mut self, c: char) -> bool {
+        let matches = self.peek() == Some(c);
+        if matches {
+            self.pos += c.len_utf8();
+        }
+
+        matches
+    }
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        match self.eat(c) {
+            true => Ok(()),
+            false => Err(self.error(&format!("Expected '{c}'"))),
+        }
+    }
+    fn error(&self, message: &str) -> Error {
+        Error(format!("{message} at position {}", self.pos))
+    }
+}
+
+/// Interprets an unquoted token as a number or boolean, or as a string otherwise
+fn parse_bare(token: &str) -> Nbt {
+    match token {
+        "true" => return Nbt::Byte(1),
+        "false" => return Nbt::Byte(0),
+        _ => {}
+    }
+
+    // Strings like `inf` or `NaN` shouldn't become numbers
+    if !token.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '.' | '+' | '-')) {
+        return Nbt::String(token.to_string());
+    }
+
+    let (number, suffix) = token.split_at(token.len() - 1);
+    let number = match suffix {
+        "b" | "B" => number.parse().ok().map(Nbt::Byte),
+        "s" | "S" => number.parse().ok().map(Nbt::Short),
+        "l" | "L" => number.parse().ok().map(Nbt::Long),
+        "f" | "F" => number.parse().ok().map(Nbt::Float),
+        "d" | "D" => number.parse().ok().map(Nbt::Double),
+        _ => None,
+    };
+
+    number
+        .or_else(|| token.parse().ok().map(Nbt::Int))
+        .or_else(|| {
+            token
+                .contains(['.', 'e', 'E'])
+                .then(|| token.parse().ok().map(Nbt::Double))
+                .flatten()
+        })
+        .unwrap_or_else(|| Nbt::String(token.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snbt_round_trip() {
+        let snbt = r#"{name:"Steve \"the\" miner",Pos:[1.5d,64.0d,-3.25d],flags:[B;1b,0b],
+            ids:[I;1,-2],seeds:[L;5L],"with space":{count:3s,big:1e3,on:true},empty:[]}"#;
+
+        let nbt: Nbt = snbt.parse().unwrap();
+        assert_eq!(nbt.get("flags"), Some(&Nbt::ByteArray(vec![1, 0])));
+        assert_eq!(
+            nbt.get("with space").unwrap().get("big"),
+            Some(&Nbt::Double(1000.0))
+        );
+        assert_eq!(
+            nbt.get("with space").unwrap().get("on"),
+            Some(&Nbt::Byte(1))
+        );
+
+        assert_eq!(nbt.to_string().parse::<Nbt>().unwrap(), nbt);
+        assert_eq!(
+            Nbt::List(vec![Nbt::String("1".into()), Nbt::String("a'b".into())]).to_string(),
+            r#"["1","a'b"]"#
+        );
+
+        assert!("[1,2b]".parse::<Nbt>().is_err());
+        assert!("[B;1,2]".parse::<Nbt>().is_err());
+        assert!("{a:1}}".parse::<Nbt>().is_err());
+        assert!("[".repeat(MAX_DEPTH + 2).parse::<Nbt>().is_err());
+    }
+}
@@ -0,0 +1,61 @@
+use crate::{FromBytes, ToBytes};
+use std::io::{self, Read, Write};
+
+/// A rotation in steps of 1/256 of a full turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Angle(pub u8);
+
+impl Angle {
+    /// Rounds to the nearest step, wrapping around so -90 is the same as 270
+    pub fn from_degrees(degrees: f32) -> Self {
+        let steps = (degrees * 256.0 / 360.0).round() as i64;
+
+        Self(steps.rem_euclid(256) as u8)
+    }
+    /// From 0 up to but not including 360
+    pub fn degrees(self) -> f32 {
+        self.0 as f32 * 360.0 / 256.0
+    }
+}
+
+impl FromBytes for Angle {
+    fn read_from<R: Read>(read: &mut R) -> io::Result<Self> {
+        Ok(Self(u8::read_from(read)?))
+    }
+}
+
+impl ToBytes for Angle {
+    fn write_to<W: Write>(&self, write: &mut W) -> io::Result<usize> {
+        self.0.write_to(write)
+    }
+    fn encoded_size(&self) -> usize {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Angle;
+    use crate::{FromBytes, ToBytes};
+
+    #[test]
+    fn angle_read_and_write() {
+        let samples: &[(f32, u8, f32)] = &[
+            (0.0, 0x00, 0.0),
+            (90.0, 0x40, 90.0),
+            (-90.0, 0xC0, 270.0),
+            (359.0, 0xFF, 358.59375),
+            (360.0, 0x00, 0.0),
+            (46.0, 0x21, 46.40625),
+        ];
+
+        for &(degrees, byte, read_degrees) in samples {
+            let mut bytes = Vec::new();
+            Angle::from_degrees(degrees).write_to(&mut bytes).unwrap();
+            assert_eq!(bytes, [byte]);
+
+            let angle = Angle::read_from(&mut &bytes[..]).unwrap();
+            assert_eq!(angle.degrees(), read_degrees);
+        }
+    }
+}
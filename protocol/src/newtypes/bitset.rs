@@ -0,0 +1,159 @@
+use crate::{FromBytes, ToBytes};
+use std::io::{self, Read, Write};
+
+/// A bit set of any length, sent as a length-prefixed array of longs
+///
+/// Bit `i` is bit `i % 64` of long `i / 64`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct BitSet(pub Vec<u64>);
+
+impl BitSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn get(&self, index: usize) -> bool {
+        self.0
+            .get(index / 64)
+            .is_some_and(|long| long >> (index % 64) & 1 == 1)
+    }
+    /// Grows the set if needed
+    pub fn set(&mut self, index: usize, value: bool) {
+        if index / 64 >= self.0.len() {
+            if !value {
+                return;
+            }
+            self.0.resize(index / 64 + 1, 0);
+        }
+
+        let long = &mut self.0[index / 64];
+        if value {
+            *long |= 1 << (index % 64);
+        } else {
+            *long &= !(1 << (index % 64));
+        }
+    }
+    /// Indices of the set bits, in order
+    pub fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.0.len() * 64).filter(|i| self.get(*i))
+    }
+}
+
+impl FromBytes for BitSet {
+    fn read_from<R: Read>(read: &mut R) -> io::Result<Self> {
+        Ok(Self(Vec::read_from(read)?))
+    }
+}
+
+impl ToBytes for BitSet {
+    fn write_to<W: Write>(&self, write: &mut W) -> io::Result<usize> {
+        self.0.write_to(write)
+    }
+    fn encoded_size(&self) -> usize {
+        self.0.encoded_size()
+    }
+}
+
+/// A bit set of `N` bits, sent as `N / 8` bytes rounded up without a length prefix
+///
+/// Bit `i` is bit `i % 8` of byte `i / 8`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FixedBitSet<const N: usize>(Box<[u8]>);
+
+impl<const N: usize> FixedBitSet<N> {
+    const BYTES: usize = N.div_ceil(8);
+
+    pub fn new() -> Self {
+        Self(vec![0; Self::BYTES].into_boxed_slice())
+    }
+    /// Panics if `index` is out of bounds
+    pub fn get(&self, index: usize) -> bool {
+        assert!(index < N, "Bit index out of bounds");
+
+        self.0[index / 8] >> (index % 8) & 1 == 1
+    }
+    /// Panics if `index` is out of bounds
+    pub fn set(&mut self, index: usize, value: bool) {
+        assert!(index < N, "Bit index out of bounds");
+
+        if value {
+            self.0[index / 8] |= 1 << (index % 8);
+        } else {
+            self.0[index / 8] &= !(1 << (index % 8));
+        }
+    }
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<const N: usize> Default for FixedBitSet<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> FromBytes for FixedBitSet<N> {
+    fn read_from<R: Read>(read: &mut R) -> io::Result<Self> {
+        let mut set = Self::new();
+        read.read_exact(&mut set.0)?;
+
+        Ok(set)
+    }
+}
+
+impl<const N: usize> ToBytes for FixedBitSet<N> {
+    fn write_to<W: Write>(&self, write: &mut W) -> io::Result<usize> {
+        write.write_all(&self.0)?;
+
+        Ok(self.0.len())
+    }
+    fn encoded_size(&self) -> usize {
+        Self::BYTES
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BitSet, FixedBitSet};
+    use crate::{FromBytes, ToBytes};
+
+    #[test]
+    fn bitset_read_and_write() {
+        let samples: &[(&[usize], &[u8])] = &[
+            (&[], &[0x00]),
+            (&[0], &[0x01, 0, 0, 0, 0, 0, 0, 0, 0x01]),
+            (
+                &[1, 64, 127],
+                &[
+                    0x02, 0, 0, 0, 0, 0, 0, 0, 0x02, 0x80, 0, 0, 0, 0, 0, 0, 0x01,
+                ],
+            ),
+        ];
+
+        for (ones, encoded) in samples {
+            let mut set = BitSet::new();
+            for i in *ones {
+                set.set(*i, true);
+            }
+
+            let mut bytes = Vec::new();
+            set.write_to(&mut bytes).unwrap();
+            assert_eq!(bytes, *encoded);
+
+            let read = BitSet::read_from(&mut &bytes[..]).unwrap();
+            assert_eq!(read.ones().collect::<Vec<_>>(), *ones);
+        }
+
+        let mut fixed = FixedBitSet::<20>::new();
+        fixed.set(0, true);
+        fixed.set(19, true);
+
+        let mut bytes = Vec::new();
+        fixed.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes, [0x01, 0x00, 0x08]);
+        assert_eq!(fixed.encoded_size(), 3);
+
+        let read = FixedBitSet::<20>::read_from(&mut &bytes[..]).unwrap();
+        assert!(read.get(0) && read.get(19) && !read.get(18));
+    }
+}
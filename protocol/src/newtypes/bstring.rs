@@ -1,16 +1,22 @@
-use crate::{FromBytes, ToBytes};
-use std::ops::Deref;
+use crate::{FromBytes, ToBytes, VarInt};
+use std::{
+    io::{self, Read},
+    ops::Deref,
+};
 
 /// Length-**B**ound **String**
 ///
+/// The bound is in UTF-16 code units, like in the vanilla protocol, which is the same
+/// as characters for most text.
+///
 /// Immutable by design
-#[derive(ToBytes, FromBytes, Debug, Clone, PartialEq)]
+#[derive(ToBytes, Debug, Clone, PartialEq)]
 pub struct BString<const MAX: usize>(String);
 
 impl<const MAX: usize> BString<MAX> {
     /// Fails if string too long
     pub fn new(s: String) -> Option<Self> {
-        if s.len() > MAX {
+        if !fits(&s, MAX) {
             return None;
         }
 
@@ -23,7 +29,7 @@ impl<const MAX: usize> BString<MAX> {
     pub fn mutate<F: FnOnce(&mut String)>(&mut self, f: F) {
         f(&mut self.0);
 
-        if self.0.len() > MAX {
+        if !fits(&self.0, MAX) {
             panic!("BString too long");
         }
     }
@@ -36,3 +42,69 @@ impl<const MAX: usize> Deref for BString<MAX> {
         &self.0
     }
 }
+
+impl<const MAX: usize> FromBytes for BString<MAX> {
+    fn read_from<R: Read>(read: &mut R) -> io::Result<Self> {
+        let length = VarInt::read_from(read)?.0;
+
+        if length < 0 {
+            return Err(invalid("Negative string length".to_string()));
+        }
+        // A UTF-16 code unit takes at most 3 bytes in UTF-8
+        if length as usize > MAX * 3 {
+            return Err(invalid(format!(
+                "String is {length} bytes long, more than allowed for {MAX} characters"
+            )));
+        }
+        crate::limits::check_length(length as usize)?;
+        crate::limits::allocate::<u8>(length as usize)?;
+
+        let mut buffer = vec![0u8; length as usize];
+        read.read_exact(&mut buffer)?;
+
+        let string =
+            String::from_utf8(buffer).map_err(|_| invalid("String not valid UTF-8".to_string()))?;
+
+        Self::new(string).ok_or_else(|| invalid(format!("String longer than {MAX} characters")))
+    }
+}
+
+/// Whether the string is at most `max` UTF-16 code units long
+fn fits(s: &str, max: usize) -> bool {
+    // Can't be longer in UTF-16 than in UTF-8
+    s.len() <= max || s.encode_utf16().count() <= max
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BString;
+    use crate::{FromBytes, ToBytes, VarInt};
+
+    #[test]
+    fn bstring_limits() {
+        // 4 characters, but 12 bytes
+        let wide = "日本語字".to_string();
+        assert!(BString::<4>::new(wide.clone()).is_some());
+        assert!(BString::<3>::new(wide.clone()).is_none());
+
+        let mut bytes = Vec::new();
+        wide.write_to(&mut bytes).unwrap();
+        assert_eq!(*BString::<4>::read_from(&mut &bytes[..]).unwrap(), wide);
+        assert!(BString::<3>::read_from(&mut &bytes[..]).is_err());
+
+        // Rejected by the length prefix alone, without the data being there
+        let mut bytes = Vec::new();
+        VarInt(i32::MAX).write_to(&mut bytes).unwrap();
+        assert!(BString::<16>::read_from(&mut &bytes[..]).is_err());
+
+        let mut bytes = Vec::new();
+        VarInt(-1).write_to(&mut bytes).unwrap();
+        assert!(BString::<16>::read_from(&mut &bytes[..]).is_err());
+        assert!(String::read_from(&mut &bytes[..]).is_err());
+        assert!(Vec::<u8>::read_from(&mut &bytes[..]).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use crate::{limits, FromBytes, ToBytes, VarInt};
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    str::FromStr,
+};
+
+/// A namespaced identifier such as `minecraft:stone`
+///
+/// Namespaces can contain `a-z`, `0-9`, `.`, `-` and `_`, and paths also `/`. Without
+/// a namespace, it's `minecraft`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Identifier {
+    namespace: String,
+    path: String,
+}
+
+/// Vanilla's limit for the whole identifier
+const MAX_LENGTH: usize = 32767;
+
+impl Identifier {
+    /// Fails if the namespace or path contains invalid characters, or the namespace is empty
+    pub fn new(namespace: impl Into<String>, path: impl Into<String>) -> Option<Self> {
+        let (namespace, path) = (namespace.into(), path.into());
+
+        let valid = !namespace.is_empty()
+            && namespace.len() + 1 + path.len() <= MAX_LENGTH
+            && namespace.chars().all(valid_namespace_char)
+            && path.chars().all(|c| valid_namespace_char(c) || c == '/');
+
+        valid.then_some(Self { namespace, path })
+    }
+    /// In the `minecraft` namespace
+    pub fn minecraft(path: impl Into<String>) -> Option<Self> {
+        Self::new("minecraft", path)
+    }
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+fn valid_namespace_char(c: char) -> bool {
+    matches!(c, 'a'..='z' | '0'..='9' | '.' | '-' | '_')
+}
+
+impl FromStr for Identifier {
+    type Err = InvalidIdentifier;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (namespace, path) = match s.split_once(':') {
+            // An empty namespace is also `minecraft`, like in vanilla
+            Some(("", path)) => ("minecraft", path),
+            Some((namespace, path)) => (namespace, path),
+            None => ("minecraft", s),
+        };
+
+        Self::new(namespace, path).ok_or_else(|| InvalidIdentifier(s.to_string()))
+    }
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.namespace, self.path)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidIdentifier(pub String);
+
+impl fmt::Display for InvalidIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid identifier {:?}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidIdentifier {}
+
+impl FromBytes for Identifier {
+    fn read_from<R: Read>(read: &mut R) -> io::Result<Self> {
+        let string = limits::with_field_limit(MAX_LENGTH, || String::read_from(read))?;
+
+        string
+            .parse()
+            .map_err(|e: InvalidIdentifier| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl ToBytes for Identifier {
+    fn write_to<W: Write>(&self, write: &mut W) -> io::Result<usize> {
+        let length = self.namespace.len() + 1 + self.path.len();
+
+        let written = VarInt(length as i32).write_to(write)?;
+        write.write_all(self.namespace.as_bytes())?;
+        write.write_all(b":")?;
+        write.write_all(self.path.as_bytes())?;
+
+        Ok(written + length)
+    }
+    fn encoded_size(&self) -> usize {
+        let length = self.namespace.len() + 1 + self.path.len();
+
+        VarInt(length as i32).encoded_size() + length
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Identifier;
+    use crate::{FromBytes, ToBytes};
+
+    #[test]
+    fn identifier_read_and_write() {
+        let samples: &[(&str, &[u8])] = &[
+            ("minecraft:stone", b"\x0Fminecraft:stone"),
+            ("stone", b"\x0Fminecraft:stone"),
+            ("bws:chat/type.1", b"\x0Fbws:chat/type.1"),
+            (":stone", b"\x0Fminecraft:stone"),
+            ("a:", b"\x02a:"),
+        ];
+
+        for (string, encoded) in samples {
+            let identifier: Identifier = string.parse().unwrap();
+
+            let mut bytes = Vec::new();
+            identifier.write_to(&mut bytes).unwrap();
+            assert_eq!(bytes, *encoded);
+            assert_eq!(identifier.encoded_size(), encoded.len());
+            assert_eq!(Identifier::read_from(&mut &bytes[..]).unwrap(), identifier);
+        }
+
+        for invalid in ["Stone", "a/b:c", "minecraft:a:b", "minecraft:é"] {
+            assert!(invalid.parse::<Identifier>().is_err());
+        }
+        assert!(Identifier::read_from(&mut &b"\x03A:b"[..]).is_err());
+    }
+}
@@ -1,9 +1,21 @@
+mod angle;
+mod bitset;
 mod bstring;
+mod identifier;
 mod nextstate;
+mod position;
+mod trailing_bytes;
 mod varint;
 mod varlong;
+mod velocity;
 
+pub use angle::Angle;
+pub use bitset::{BitSet, FixedBitSet};
 pub use bstring::BString;
+pub use identifier::{Identifier, InvalidIdentifier};
 pub use nextstate::NextState;
+pub use position::Position;
+pub use trailing_bytes::TrailingBytes;
 pub use varint::VarInt;
 pub use varlong::VarLong;
+pub use velocity::{LpVec3, Velocity};
@@ -0,0 +1,88 @@
+use crate::{FromBytes, ToBytes};
+use std::io::{self, Read, Write};
+
+/// A block position, packed into 64 bits
+///
+/// x and z take 26 bits each and y 12 bits, in that order from the most significant
+/// bits, so x and z range from -33554432 to 33554431 and y from -2048 to 2047.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl Position {
+    pub fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+    /// Fails if a coordinate is out of range
+    pub fn packed(self) -> Option<i64> {
+        let fits = |value: i32, bits: u32| value >> (bits - 1) == 0 || value >> (bits - 1) == -1;
+        if !fits(self.x, 26) || !fits(self.z, 26) || !fits(self.y, 12) {
+            return None;
+        }
+
+        let (x, y, z) = (self.x as i64, self.y as i64, self.z as i64);
+
+        Some((x & 0x3FFFFFF) << 38 | (z & 0x3FFFFFF) << 12 | (y & 0xFFF))
+    }
+    pub fn from_packed(packed: i64) -> Self {
+        // Arithmetic shifts extend the sign
+        Self {
+            x: (packed >> 38) as i32,
+            y: (packed << 52 >> 52) as i32,
+            z: (packed << 26 >> 38) as i32,
+        }
+    }
+}
+
+impl FromBytes for Position {
+    fn read_from<R: Read>(read: &mut R) -> io::Result<Self> {
+        Ok(Self::from_packed(i64::read_from(read)?))
+    }
+}
+
+impl ToBytes for Position {
+    fn write_to<W: Write>(&self, write: &mut W) -> io::Result<usize> {
+        let packed = self
+            .packed()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Position out of range"))?;
+
+        packed.write_to(write)
+    }
+    fn encoded_size(&self) -> usize {
+        8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Position;
+    use crate::{FromBytes, ToBytes};
+
+    #[test]
+    fn position_read_and_write() {
+        let samples: &[((i32, i32, i32), u64)] = &[
+            ((0, 0, 0), 0),
+            ((1, 2, 3), 0x0000_0040_0000_3002),
+            ((-1, -1, -1), 0xFFFF_FFFF_FFFF_FFFF),
+            ((18357644, 831, -20882616), 0x4607_632C_15B4_833F),
+            ((33554431, 2047, -33554432), 0x7FFF_FFE0_0000_07FF),
+        ];
+
+        for &((x, y, z), packed) in samples {
+            let position = Position::new(x, y, z);
+
+            let mut bytes = Vec::new();
+            position.write_to(&mut bytes).unwrap();
+            assert_eq!(bytes, packed.to_be_bytes());
+            assert_eq!(Position::read_from(&mut &bytes[..]).unwrap(), position);
+        }
+
+        assert!(Position::new(0, 2048, 0).write_to(&mut Vec::new()).is_err());
+        assert!(Position::new(1 << 25, 0, 0)
+            .write_to(&mut Vec::new())
+            .is_err());
+    }
+}
@@ -0,0 +1,5 @@
+/// The rest of the packet, borrowed from the packet buffer
+///
+/// Like `Box<[u8]>`, it's not length-prefixed, so it can only be the last field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrailingBytes<'a>(pub &'a [u8]);
@@ -40,6 +40,7 @@ mod tests {
             VarInt(sample.0).write_to(&mut bytes).unwrap();
 
             assert_eq!(bytes, sample.1);
+            assert_eq!(VarInt(sample.0).encoded_size(), sample.1.len());
         }
 
         // Reading...
@@ -0,0 +1,187 @@
+use crate::{FromBytes, ToBytes, VarInt};
+use std::io::{self, Read, Write};
+
+/// A velocity in blocks per tick, sent as a short for each axis in 1/8000 blocks per tick
+///
+/// Limited to ±3.9 blocks per tick, like in vanilla.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Velocity {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Velocity {
+    const MAX: f64 = 3.9;
+
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl FromBytes for Velocity {
+    fn read_from<R: Read>(read: &mut R) -> io::Result<Self> {
+        let mut axis = || Ok::<_, io::Error>(i16::read_from(read)? as f64 / 8000.0);
+
+        Ok(Self::new(axis()?, axis()?, axis()?))
+    }
+}
+
+impl ToBytes for Velocity {
+    fn write_to<W: Write>(&self, write: &mut W) -> io::Result<usize> {
+        let mut written = 0;
+        for axis in [self.x, self.y, self.z] {
+            let axis = (axis.clamp(-Self::MAX, Self::MAX) * 8000.0) as i16;
+            written += axis.write_to(write)?;
+        }
+
+        Ok(written)
+    }
+    fn encoded_size(&self) -> usize {
+        6
+    }
+}
+
+/// A low precision vector, for velocities since 1.21.9
+///
+/// The components are scaled to -1..1 by the largest one rounded up, and packed with 15
+/// bits each into 6 bytes, followed by the scale as a VarInt if it doesn't fit into 2 bits.
+/// Vectors with all components close to 0 are sent as a single 0 byte.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LpVec3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl LpVec3 {
+    /// Largest value that is sent as 0
+    const ZERO_THRESHOLD: f64 = 3.051944088384301E-5;
+    /// Components are clamped to this
+    const MAX: f64 = 1.7179869183E10;
+
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    /// The components and scale as sent
+    fn packed(self) -> Option<(u64, u64)> {
+        let sanitize = |v: f64| match v.is_nan() {
+            true => 0.0,
+            false => v.clamp(-Self::MAX, Self::MAX),
+        };
+        let (x, y, z) = (sanitize(self.x), sanitize(self.y), sanitize(self.z));
+
+        let max = x.abs().max(y.abs()).max(z.abs());
+        if max < Self::ZERO_THRESHOLD {
+            return None;
+        }
+
+        let scale = max.ceil() as u64;
+        let pack = |v: f64| ((v / scale as f64 * 0.5 + 0.5) * 32766.0).round() as u64;
+        let flags = match scale > 3 {
+            true => scale & 3 | 4,
+            false => scale,
+        };
+
+        Some((flags | pack(x) << 3 | pack(y) << 18 | pack(z) << 33, scale))
+    }
+}
+
+impl FromBytes for LpVec3 {
+    fn read_from<R: Read>(read: &mut R) -> io::Result<Self> {
+        let first = u8::read_from(read)?;
+        if first == 0 {
+            return Ok(Self::default());
+        }
+
+        let second = u8::read_from(read)?;
+        let rest = u32::read_from(read)?;
+        let packed = (rest as u64) << 16 | (second as u64) << 8 | first as u64;
+
+        let mut scale = (first & 3) as u64;
+        if first & 4 != 0 {
+            scale |= (VarInt::read_from(read)?.0 as u32 as u64) << 2;
+        }
+
+        let unpack = |shift: u32| {
+            let v = (packed >> shift & 0x7FFF).min(32766) as f64;
+            (v * 2.0 / 32766.0 - 1.0) * scale as f64
+        };
+
+        Ok(Self::new(unpack(3), unpack(18), unpack(33)))
+    }
+}
+
+impl ToBytes for LpVec3 {
+    fn write_to<W: Write>(&self, write: &mut W) -> io::Result<usize> {
+        let Some((packed, scale)) = self.packed() else {
+            return 0u8.write_to(write);
+        };
+
+        let mut written = (packed as u8).write_to(write)?;
+        written += ((packed >> 8) as u8).write_to(write)?;
+        written += ((packed >> 16) as u32).write_to(write)?;
+        if scale > 3 {
+            written += VarInt((scale >> 2) as i32).write_to(write)?;
+        }
+
+        Ok(written)
+    }
+    fn encoded_size(&self) -> usize {
+        match self.packed() {
+            None => 1,
+            Some((_, scale)) if scale > 3 => 6 + VarInt((scale >> 2) as i32).encoded_size(),
+            Some(_) => 6,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LpVec3, Velocity};
+    use crate::{FromBytes, ToBytes};
+
+    type Xyz = (f64, f64, f64);
+
+    #[test]
+    fn velocity_read_and_write() {
+        let samples: &[(Xyz, &[u8], Xyz)] = &[
+            ((0.0, 0.0, 0.0), &[0, 0, 0, 0, 0, 0], (0.0, 0.0, 0.0)),
+            (
+                (0.5, -0.08, 10.0),
+                &[0x0F, 0xA0, 0xFD, 0x80, 0x79, 0xE0],
+                (0.5, -0.08, 3.9),
+            ),
+        ];
+
+        for ((x, y, z), encoded, read) in samples {
+            let mut bytes = Vec::new();
+            Velocity::new(*x, *y, *z).write_to(&mut bytes).unwrap();
+            assert_eq!(bytes, *encoded);
+
+            let velocity = Velocity::read_from(&mut &bytes[..]).unwrap();
+            assert_eq!((velocity.x, velocity.y, velocity.z), *read);
+        }
+
+        let samples: &[(Xyz, &[u8])] = &[
+            ((0.0, 0.00001, 0.0), &[0x00]),
+            ((0.0, -1.0, 0.0), &[0xF9, 0xFF, 0x7F, 0xFE, 0x00, 0x01]),
+            ((0.0, 0.0, 8.0), &[0xFC, 0xFF, 0xFF, 0xFC, 0xFF, 0xFD, 0x02]),
+        ];
+
+        for ((x, y, z), encoded) in samples {
+            let vec = LpVec3::new(*x, *y, *z);
+
+            let mut bytes = Vec::new();
+            vec.write_to(&mut bytes).unwrap();
+            assert_eq!(bytes, *encoded);
+            assert_eq!(vec.encoded_size(), encoded.len());
+
+            let read = LpVec3::read_from(&mut &bytes[..]).unwrap();
+            for (read, original) in [(read.x, x), (read.y, y), (read.z, z)] {
+                assert!((read - original).abs() < 0.001, "{read} != {original}");
+            }
+        }
+    }
+}
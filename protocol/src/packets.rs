@@ -1,11 +1,16 @@
+pub mod configuration;
 pub mod handshake;
 pub mod login;
+pub mod play;
 pub mod status;
 
-use crate::{FromBytes, ToBytes};
+use crate::{version::ProtocolVersion, FromBytes, ToBytes, ToBytesVersioned};
+pub use configuration::{CBConfiguration, SBConfiguration};
 pub use handshake::SBHandshake;
 pub use login::{CBLogin, SBLogin};
+pub use play::{CBPlay, SBPlay};
 pub use status::{CBStatus, SBStatus};
+use std::io::{Result, Write};
 
 #[derive(FromBytes, ToBytes)]
 struct NoTag;
@@ -21,7 +26,8 @@ pub enum ServerBound {
     Handshake(SBHandshake),
     Status(SBStatus),
     Login(SBLogin),
-    Play,
+    Configuration(SBConfiguration),
+    Play(SBPlay),
 }
 
 #[derive(ToBytes, Debug, PartialEq, Clone)]
@@ -29,7 +35,35 @@ pub enum ServerBound {
 pub enum ClientBound {
     Status(CBStatus),
     Login(CBLogin),
-    Play,
+    Configuration(CBConfiguration),
+    Play(CBPlay),
+}
+
+impl ToBytesVersioned for ClientBound {
+    fn write_versioned<W: Write>(&self, write: &mut W, version: ProtocolVersion) -> Result<usize> {
+        match self {
+            Self::Status(packet) => packet.write_versioned(write, version),
+            Self::Login(packet) => packet.write_versioned(write, version),
+            Self::Configuration(packet) => packet.write_versioned(write, version),
+            Self::Play(packet) => packet.write_versioned(write, version),
+        }
+    }
+    fn versioned_size(&self, version: ProtocolVersion) -> usize {
+        match self {
+            Self::Status(packet) => packet.versioned_size(version),
+            Self::Login(packet) => packet.versioned_size(version),
+            Self::Configuration(packet) => packet.versioned_size(version),
+            Self::Play(packet) => packet.versioned_size(version),
+        }
+    }
+    fn is_available(&self, version: ProtocolVersion) -> bool {
+        match self {
+            Self::Status(packet) => packet.is_available(version),
+            Self::Login(packet) => packet.is_available(version),
+            Self::Configuration(packet) => packet.is_available(version),
+            Self::Play(packet) => packet.is_available(version),
+        }
+    }
 }
 
 impl From<SBHandshake> for ServerBound {
@@ -61,3 +95,27 @@ impl From<CBLogin> for ClientBound {
         Self::Login(value)
     }
 }
+
+impl From<SBConfiguration> for ServerBound {
+    fn from(value: SBConfiguration) -> Self {
+        Self::Configuration(value)
+    }
+}
+
+impl From<CBConfiguration> for ClientBound {
+    fn from(value: CBConfiguration) -> Self {
+        Self::Configuration(value)
+    }
+}
+
+impl From<SBPlay> for ServerBound {
+    fn from(value: SBPlay) -> Self {
+        Self::Play(value)
+    }
+}
+
+impl From<CBPlay> for ClientBound {
+    fn from(value: CBPlay) -> Self {
+        Self::Play(value)
+    }
+}
@@ -0,0 +1,160 @@
+//! Packets of the configuration state, between login and play since 1.20.2
+//!
+//! The server sends the registries, feature flags, tags and resource packs, and the
+//! client its settings, before the server finishes the configuration.
+
+use super::{
+    login::Disconnect,
+    play::{KeepAlive, PluginMessage},
+};
+use crate::{
+    nbt::NetworkNbt, BString, FromBytes, FromBytesVersioned, TextComponent, ToBytes,
+    ToBytesVersioned, VarInt,
+};
+
+#[derive(FromBytes, ToBytes, FromBytesVersioned, ToBytesVersioned, Debug, Clone, PartialEq)]
+pub enum SBConfiguration {
+    ClientInformation(ClientInformation),
+    PluginMessage(PluginMessage),
+    /// Acknowledges [`CBConfiguration::FinishConfiguration`], switching to the play state
+    FinishConfiguration,
+    KeepAlive(KeepAlive),
+    Pong(Pong),
+    ResourcePackResponse(ResourcePackResponse),
+}
+
+#[derive(FromBytes, ToBytes, FromBytesVersioned, ToBytesVersioned, Debug, Clone, PartialEq)]
+pub enum CBConfiguration {
+    PluginMessage(PluginMessage),
+    Disconnect(Disconnect),
+    FinishConfiguration,
+    KeepAlive(KeepAlive),
+    Ping(Ping),
+    RegistryData(RegistryData),
+    ResourcePack(ResourcePack),
+    FeatureFlags(FeatureFlags),
+    UpdateTags(UpdateTags),
+}
+
+/// The client's settings, also sent in the play state when they change
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct ClientInformation {
+    /// Such as `en_us`
+    pub locale: BString<16>,
+    pub view_distance: i8,
+    /// 0 for full, 1 for commands only, 2 for hidden
+    #[varint]
+    pub chat_mode: i32,
+    pub chat_colors: bool,
+    /// Bit mask of the skin layers that are shown
+    pub displayed_skin_parts: u8,
+    /// 0 for left, 1 for right
+    #[varint]
+    pub main_hand: i32,
+    pub enable_text_filtering: bool,
+    pub allow_server_listings: bool,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct Ping {
+    pub id: i32,
+}
+
+/// Answers [`Ping`] with the same id
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct Pong {
+    pub id: i32,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct ResourcePackResponse {
+    /// 0 when loaded, 1 when declined, 2 when the download failed, 3 when accepted
+    /// and the download starts
+    #[varint]
+    pub result: i32,
+}
+
+/// All the registries, such as dimension types, biomes and chat types
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct RegistryData {
+    /// A compound of each registry's name to a compound with its `type` and the entries
+    /// as `value`, each with a `name`, `id` and `element`
+    pub registry_codec: NetworkNbt,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct ResourcePack {
+    pub url: String,
+    /// Hex encoded SHA-1 hash of the pack, or empty
+    pub hash: BString<40>,
+    /// Whether the client is disconnected if it declines the pack
+    pub forced: bool,
+    pub prompt_message: Option<TextComponent>,
+}
+
+/// Enables experimental features, `minecraft:vanilla` for none
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct FeatureFlags {
+    pub feature_flags: Vec<String>,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct UpdateTags {
+    pub registries: Vec<RegistryTags>,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct RegistryTags {
+    /// Such as `minecraft:block`
+    pub registry: String,
+    pub tags: Vec<Tag>,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct Tag {
+    pub name: String,
+    /// Ids in the registry
+    pub entries: Vec<VarInt>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        packets::{login::LoginStart, SBLogin},
+        version::ForVersion,
+        ProtocolVersion,
+    };
+    use uuid::Uuid;
+
+    #[test]
+    fn configuration_packets() {
+        let mut bytes = Vec::new();
+        CBConfiguration::FeatureFlags(FeatureFlags {
+            feature_flags: vec!["minecraft:vanilla".to_string()],
+        })
+        .write_to(&mut bytes)
+        .unwrap();
+        assert_eq!(bytes, b"\x07\x01\x11minecraft:vanilla");
+
+        let finish = SBConfiguration::read_versioned(&mut &b"\x02"[..], ProtocolVersion::V1_20_2);
+        assert_eq!(finish.unwrap(), SBConfiguration::FinishConfiguration);
+
+        // The UUID isn't optional since 1.20.2
+        let start = SBLogin::LoginStart(LoginStart {
+            name: BString::new("Steve".to_string()).unwrap(),
+            uuid: Some(Uuid::nil()),
+        });
+        let mut bytes = Vec::new();
+        ForVersion(&start, ProtocolVersion::V1_20_2)
+            .write_to(&mut bytes)
+            .unwrap();
+        assert_eq!(bytes.len(), 1 + 6 + 16);
+        assert_eq!(
+            SBLogin::read_versioned(&mut &bytes[..], ProtocolVersion::V1_20_2).unwrap(),
+            start
+        );
+        // Login acknowledged, which didn't exist before
+        assert!(SBLogin::read_versioned(&mut &b"\x03"[..], ProtocolVersion::V1_20).is_err());
+    }
+}
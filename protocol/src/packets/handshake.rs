@@ -1,6 +1,8 @@
-use crate::{newtypes::NextState, BString, FromBytes, ToBytes, VarInt};
+use crate::{
+    newtypes::NextState, BString, FromBytes, FromBytesVersioned, ToBytes, ToBytesVersioned, VarInt,
+};
 
-#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+#[derive(FromBytes, ToBytes, FromBytesVersioned, ToBytesVersioned, Debug, Clone, PartialEq)]
 pub enum SBHandshake {
     Handshake(Handshake),
 }
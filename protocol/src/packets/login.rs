@@ -1,15 +1,20 @@
-use crate::{BString, FromBytes, ToBytes, VarInt};
-use serde_json::Value as JsonValue;
+use crate::{
+    BString, FromBytes, FromBytesVersioned, TextComponent, ToBytes, ToBytesVersioned, VarInt,
+};
 use uuid::Uuid;
 
-#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+#[derive(FromBytes, ToBytes, FromBytesVersioned, ToBytesVersioned, Debug, Clone, PartialEq)]
 pub enum SBLogin {
+    #[layout(LoginStart1_20_2, since = 764)]
     LoginStart(LoginStart),
     EncryptionResponse(EncryptionResponse),
     PluginResponse(PluginResponse),
+    /// Switches to the configuration state after [`LoginSuccess`]
+    #[id(0x03, since = 764)]
+    LoginAcknowledged,
 }
 
-#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+#[derive(FromBytes, ToBytes, FromBytesVersioned, ToBytesVersioned, Debug, Clone, PartialEq)]
 pub enum CBLogin {
     Disconnect(Disconnect),
     EncryptionRequest(EncryptionRequest),
@@ -24,6 +29,31 @@ pub struct LoginStart {
     pub uuid: Option<Uuid>,
 }
 
+/// Since 1.20.2, which made the UUID required
+#[derive(FromBytes, ToBytes)]
+struct LoginStart1_20_2 {
+    name: BString<16>,
+    uuid: Uuid,
+}
+
+impl From<LoginStart1_20_2> for LoginStart {
+    fn from(new: LoginStart1_20_2) -> Self {
+        Self {
+            name: new.name,
+            uuid: Some(new.uuid),
+        }
+    }
+}
+
+impl From<&LoginStart> for LoginStart1_20_2 {
+    fn from(start: &LoginStart) -> Self {
+        Self {
+            name: start.name.clone(),
+            uuid: start.uuid.unwrap_or_default(),
+        }
+    }
+}
+
 #[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
 pub struct EncryptionResponse {
     pub shared_secret: Vec<u8>,
@@ -38,7 +68,7 @@ pub struct PluginResponse {
 
 #[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
 pub struct Disconnect {
-    pub reason: JsonValue, // todo chat object newtype
+    pub reason: TextComponent,
 }
 
 #[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
@@ -71,5 +101,6 @@ pub struct SetCompression {
 pub struct PluginRequest {
     pub message_id: VarInt,
     pub channel: String,
+    #[prefixed(none)]
     pub data: Box<[u8]>,
 }
@@ -0,0 +1,892 @@
+//! Packets of the play state
+//!
+//! Play packet ids change between versions, so these can only be read and written for a
+//! protocol version. `ToBytes` writes them with the ids of [`ProtocolVersion::LATEST`].
+//! They are mapped up to 1.20.2.
+
+use super::login::Disconnect;
+use crate::{
+    chunk::{ChunkSection, LightArray},
+    nbt::{Compound, NamedNbt, Nbt, NetworkNbt},
+    newtypes::{BitSet, FixedBitSet, Position, Velocity},
+    FromBytes, FromBytesVersioned, ProtocolVersion, TextComponent, ToBytes, ToBytesVersioned,
+    VarInt,
+};
+use std::io::{Result, Write};
+use uuid::Uuid;
+
+#[derive(FromBytesVersioned, ToBytesVersioned, Debug, Clone, PartialEq)]
+pub enum SBPlay {
+    #[id(0x00)]
+    ConfirmTeleportation(ConfirmTeleportation),
+    #[id(0x04)]
+    ChatCommand(ChatCommand),
+    #[id(0x05)]
+    ChatMessage(ChatMessage),
+    #[id(0x0C, until = 761)]
+    #[id(0x0D, since = 762, until = 763)]
+    #[id(0x0F, since = 764)]
+    PluginMessage(PluginMessage),
+    #[id(0x11, until = 761)]
+    #[id(0x12, since = 762, until = 763)]
+    #[id(0x14, since = 764)]
+    KeepAlive(KeepAlive),
+    #[id(0x13, until = 761)]
+    #[id(0x14, since = 762, until = 763)]
+    #[id(0x16, since = 764)]
+    SetPlayerPosition(SetPlayerPosition),
+    #[id(0x14, until = 761)]
+    #[id(0x15, since = 762, until = 763)]
+    #[id(0x17, since = 764)]
+    SetPlayerPositionAndRotation(SetPlayerPositionAndRotation),
+    #[id(0x15, until = 761)]
+    #[id(0x16, since = 762, until = 763)]
+    #[id(0x18, since = 764)]
+    SetPlayerRotation(SetPlayerRotation),
+    #[id(0x16, until = 761)]
+    #[id(0x17, since = 762, until = 763)]
+    #[id(0x19, since = 764)]
+    SetPlayerOnGround(SetPlayerOnGround),
+}
+
+#[derive(FromBytesVersioned, ToBytesVersioned, Debug, Clone, PartialEq)]
+pub enum CBPlay {
+    #[id(0x00, until = 761)]
+    #[id(0x01, since = 762)]
+    SpawnEntity(SpawnEntity),
+    #[id(0x09, until = 761)]
+    #[id(0x0A, since = 762, until = 763)]
+    #[id(0x09, since = 764)]
+    BlockUpdate(BlockUpdate),
+    #[id(0x15, until = 761)]
+    #[id(0x17, since = 762, until = 763)]
+    #[id(0x18, since = 764)]
+    PluginMessage(PluginMessage),
+    #[id(0x17, until = 761)]
+    #[id(0x1A, since = 762, until = 763)]
+    #[id(0x1B, since = 764)]
+    Disconnect(Disconnect),
+    #[id(0x1B, until = 761)]
+    #[id(0x1E, since = 762, until = 763)]
+    #[id(0x1F, since = 764)]
+    #[layout(UnloadChunk1_20, until = 763)]
+    UnloadChunk(UnloadChunk),
+    #[id(0x1F, until = 761)]
+    #[id(0x23, since = 762, until = 763)]
+    #[id(0x24, since = 764)]
+    KeepAlive(KeepAlive),
+    #[id(0x20, until = 761)]
+    #[id(0x24, since = 762, until = 763)]
+    #[id(0x25, since = 764)]
+    #[layout(ChunkData1_19, until = 762)]
+    #[layout(ChunkData1_20, until = 763)]
+    ChunkData(ChunkData),
+    #[id(0x24, until = 761)]
+    #[id(0x28, since = 762, until = 763)]
+    #[id(0x29, since = 764)]
+    #[layout(Login1_19, until = 762)]
+    #[layout(Login1_20, until = 763)]
+    Login(Login),
+    #[id(0x27, until = 761)]
+    #[id(0x2B, since = 762, until = 763)]
+    #[id(0x2C, since = 764)]
+    UpdateEntityPosition(UpdateEntityPosition),
+    #[id(0x28, until = 761)]
+    #[id(0x2C, since = 762, until = 763)]
+    #[id(0x2D, since = 764)]
+    UpdateEntityPositionAndRotation(UpdateEntityPositionAndRotation),
+    #[id(0x29, until = 761)]
+    #[id(0x2D, since = 762, until = 763)]
+    #[id(0x2E, since = 764)]
+    UpdateEntityRotation(UpdateEntityRotation),
+    #[id(0x38, until = 761)]
+    #[id(0x3C, since = 762, until = 763)]
+    #[id(0x3E, since = 764)]
+    #[layout(SynchronizePlayerPosition1_19_3, until = 761)]
+    SynchronizePlayerPosition(SynchronizePlayerPosition),
+    #[id(0x3A, until = 761)]
+    #[id(0x3E, since = 762, until = 763)]
+    #[id(0x40, since = 764)]
+    RemoveEntities(RemoveEntities),
+    #[id(0x4A, until = 761)]
+    #[id(0x4E, since = 762, until = 763)]
+    #[id(0x50, since = 764)]
+    SetCenterChunk(SetCenterChunk),
+    #[id(0x4C, until = 761)]
+    #[id(0x50, since = 762, until = 763)]
+    #[id(0x52, since = 764)]
+    SetDefaultSpawnPosition(SetDefaultSpawnPosition),
+    #[id(0x60, until = 761)]
+    #[id(0x64, since = 762, until = 763)]
+    #[id(0x67, since = 764)]
+    SystemChatMessage(SystemChatMessage),
+}
+
+impl ToBytes for SBPlay {
+    fn write_to<W: Write>(&self, write: &mut W) -> Result<usize> {
+        self.write_versioned(write, ProtocolVersion::LATEST)
+    }
+    fn encoded_size(&self) -> usize {
+        self.versioned_size(ProtocolVersion::LATEST)
+    }
+}
+
+impl ToBytes for CBPlay {
+    fn write_to<W: Write>(&self, write: &mut W) -> Result<usize> {
+        self.write_versioned(write, ProtocolVersion::LATEST)
+    }
+    fn encoded_size(&self) -> usize {
+        self.versioned_size(ProtocolVersion::LATEST)
+    }
+}
+
+/// Same in both directions, the client answers with the id the server sent
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct KeepAlive {
+    pub id: i64,
+}
+
+/// Same in both directions
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct PluginMessage {
+    pub channel: String,
+    #[prefixed(none)]
+    pub data: Box<[u8]>,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct ConfirmTeleportation {
+    #[varint]
+    pub teleport_id: i32,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct ChatCommand {
+    /// Without the leading `/`
+    #[max_len(256)]
+    pub command: String,
+    pub timestamp: i64,
+    pub salt: i64,
+    pub argument_signatures: Vec<ArgumentSignature>,
+    #[varint]
+    pub message_count: i32,
+    /// The last 20 messages seen by the client
+    pub acknowledged: FixedBitSet<20>,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct ArgumentSignature {
+    pub argument_name: String,
+    pub signature: [u8; 256],
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    #[max_len(256)]
+    pub message: String,
+    pub timestamp: i64,
+    pub salt: i64,
+    pub signature: Option<Box<[u8; 256]>>,
+    #[varint]
+    pub message_count: i32,
+    /// The last 20 messages seen by the client
+    pub acknowledged: FixedBitSet<20>,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct SetPlayerPosition {
+    pub x: f64,
+    /// Of the feet
+    pub y: f64,
+    pub z: f64,
+    pub on_ground: bool,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct SetPlayerPositionAndRotation {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct SetPlayerRotation {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct SetPlayerOnGround {
+    pub on_ground: bool,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct SpawnEntity {
+    #[varint]
+    pub entity_id: i32,
+    pub entity_uuid: Uuid,
+    /// Id in the `minecraft:entity_type` registry
+    #[varint]
+    pub entity_type: i32,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    #[angle]
+    pub pitch: f32,
+    #[angle]
+    pub yaw: f32,
+    #[angle]
+    pub head_yaw: f32,
+    /// Meaning depends on the entity type
+    #[varint]
+    pub data: i32,
+    pub velocity: Velocity,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct BlockUpdate {
+    pub location: Position,
+    /// Id of the block state
+    #[varint]
+    pub block_id: i32,
+}
+
+/// Since 1.20.2 the coordinates are read as one `i64`, z in the upper half
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct UnloadChunk {
+    pub chunk_z: i32,
+    pub chunk_x: i32,
+}
+
+/// Before 1.20.2, which swapped the coordinates
+#[derive(FromBytes, ToBytes)]
+struct UnloadChunk1_20 {
+    chunk_x: i32,
+    chunk_z: i32,
+}
+
+impl From<UnloadChunk1_20> for UnloadChunk {
+    fn from(old: UnloadChunk1_20) -> Self {
+        Self {
+            chunk_z: old.chunk_z,
+            chunk_x: old.chunk_x,
+        }
+    }
+}
+
+impl From<&UnloadChunk> for UnloadChunk1_20 {
+    fn from(unload: &UnloadChunk) -> Self {
+        Self {
+            chunk_x: unload.chunk_x,
+            chunk_z: unload.chunk_z,
+        }
+    }
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct ChunkData {
+    pub chunk_x: i32,
+    pub chunk_z: i32,
+    pub heightmaps: NetworkNbt,
+    /// The chunk sections, from the bottom of the world up, see [`ChunkData::sections`]
+    pub data: Vec<u8>,
+    pub block_entities: Vec<ChunkBlockEntity>,
+    pub light: LightData,
+}
+
+impl ChunkData {
+    /// Reads the sections from `data`
+    pub fn sections(&self) -> Result<Vec<ChunkSection>> {
+        let mut data = &self.data[..];
+        let mut sections = Vec::new();
+        while !data.is_empty() {
+            sections.push(ChunkSection::read_from(&mut data)?);
+        }
+
+        Ok(sections)
+    }
+    /// Writes the sections to `data`, from the bottom of the world up
+    pub fn set_sections(&mut self, sections: &[ChunkSection]) -> Result<()> {
+        // Not presized, as that would find the palettes of each section twice
+        self.data.clear();
+        for section in sections {
+            section.write_to(&mut self.data)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Before 1.20, which removed `trust_edges`
+#[derive(FromBytes, ToBytes)]
+struct ChunkData1_19 {
+    chunk_x: i32,
+    chunk_z: i32,
+    heightmaps: NamedNbt,
+    data: Vec<u8>,
+    block_entities: Vec<ChunkBlockEntity1_20>,
+    trust_edges: bool,
+    light: LightData,
+}
+
+impl From<ChunkData1_19> for ChunkData {
+    fn from(old: ChunkData1_19) -> Self {
+        Self {
+            chunk_x: old.chunk_x,
+            chunk_z: old.chunk_z,
+            heightmaps: NetworkNbt(Some(old.heightmaps.value)),
+            data: old.data,
+            block_entities: old.block_entities.into_iter().map(Into::into).collect(),
+            light: old.light,
+        }
+    }
+}
+
+impl From<&ChunkData> for ChunkData1_19 {
+    fn from(chunk: &ChunkData) -> Self {
+        Self {
+            chunk_x: chunk.chunk_x,
+            chunk_z: chunk.chunk_z,
+            heightmaps: named(&chunk.heightmaps),
+            data: chunk.data.clone(),
+            block_entities: chunk.block_entities.iter().map(Into::into).collect(),
+            trust_edges: true,
+            light: chunk.light.clone(),
+        }
+    }
+}
+
+/// Before 1.20.2, which made the root tags of NBT nameless
+#[derive(FromBytes, ToBytes)]
+struct ChunkData1_20 {
+    chunk_x: i32,
+    chunk_z: i32,
+    heightmaps: NamedNbt,
+    data: Vec<u8>,
+    block_entities: Vec<ChunkBlockEntity1_20>,
+    light: LightData,
+}
+
+impl From<ChunkData1_20> for ChunkData {
+    fn from(old: ChunkData1_20) -> Self {
+        Self {
+            chunk_x: old.chunk_x,
+            chunk_z: old.chunk_z,
+            heightmaps: NetworkNbt(Some(old.heightmaps.value)),
+            data: old.data,
+            block_entities: old.block_entities.into_iter().map(Into::into).collect(),
+            light: old.light,
+        }
+    }
+}
+
+impl From<&ChunkData> for ChunkData1_20 {
+    fn from(chunk: &ChunkData) -> Self {
+        Self {
+            chunk_x: chunk.chunk_x,
+            chunk_z: chunk.chunk_z,
+            heightmaps: named(&chunk.heightmaps),
+            data: chunk.data.clone(),
+            block_entities: chunk.block_entities.iter().map(Into::into).collect(),
+            light: chunk.light.clone(),
+        }
+    }
+}
+
+/// NBT as sent before 1.20.2, with an empty root name. Missing NBT becomes an empty
+/// compound, as the format can't represent it.
+fn named(nbt: &NetworkNbt) -> NamedNbt {
+    NamedNbt {
+        name: String::new(),
+        value: nbt
+            .0
+            .clone()
+            .unwrap_or_else(|| Nbt::Compound(Compound::new())),
+    }
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct ChunkBlockEntity {
+    /// Coordinates within the chunk, `x << 4 | z`
+    pub packed_xz: u8,
+    pub y: i16,
+    /// Id in the `minecraft:block_entity_type` registry
+    #[varint]
+    pub block_entity_type: i32,
+    pub data: NetworkNbt,
+}
+
+/// Before 1.20.2, which made the root tag of `data` nameless
+#[derive(FromBytes, ToBytes)]
+struct ChunkBlockEntity1_20 {
+    packed_xz: u8,
+    y: i16,
+    #[varint]
+    block_entity_type: i32,
+    data: NamedNbt,
+}
+
+impl From<ChunkBlockEntity1_20> for ChunkBlockEntity {
+    fn from(old: ChunkBlockEntity1_20) -> Self {
+        Self {
+            packed_xz: old.packed_xz,
+            y: old.y,
+            block_entity_type: old.block_entity_type,
+            data: NetworkNbt(Some(old.data.value)),
+        }
+    }
+}
+
+impl From<&ChunkBlockEntity> for ChunkBlockEntity1_20 {
+    fn from(entity: &ChunkBlockEntity) -> Self {
+        Self {
+            packed_xz: entity.packed_xz,
+            y: entity.y,
+            block_entity_type: entity.block_entity_type,
+            data: named(&entity.data),
+        }
+    }
+}
+
+/// Light of a column of chunk sections, including one below and one above the world
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq, Default)]
+pub struct LightData {
+    /// Sections with sky light, one array each in `sky_light_arrays`
+    pub sky_light_mask: BitSet,
+    pub block_light_mask: BitSet,
+    /// Sections with all sky light 0
+    pub empty_sky_light_mask: BitSet,
+    pub empty_block_light_mask: BitSet,
+    pub sky_light_arrays: Vec<LightArray>,
+    pub block_light_arrays: Vec<LightArray>,
+}
+
+/// Join game
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct Login {
+    pub entity_id: i32,
+    pub is_hardcore: bool,
+    pub dimension_names: Vec<String>,
+    /// Unused by the client
+    #[varint]
+    pub max_players: i32,
+    #[varint]
+    pub view_distance: i32,
+    #[varint]
+    pub simulation_distance: i32,
+    pub reduced_debug_info: bool,
+    pub enable_respawn_screen: bool,
+    /// Added in 1.20.2
+    pub do_limited_crafting: bool,
+    pub dimension_type: String,
+    pub dimension_name: String,
+    /// First 8 bytes of the SHA-256 hash of the world seed
+    pub hashed_seed: i64,
+    pub game_mode: u8,
+    /// -1 if there is none
+    pub previous_game_mode: i8,
+    pub is_debug: bool,
+    pub is_flat: bool,
+    pub death_location: Option<DeathLocation>,
+    /// Added in 1.20
+    #[varint]
+    pub portal_cooldown: i32,
+    /// The dimension types, biomes, chat types and damage types. Only sent in this packet
+    /// before 1.20.2, which sends them in the configuration state instead.
+    #[skip]
+    pub registry_codec: NetworkNbt,
+}
+
+/// Before 1.20, which added `portal_cooldown`
+#[derive(FromBytes, ToBytes)]
+struct Login1_19 {
+    entity_id: i32,
+    is_hardcore: bool,
+    game_mode: u8,
+    previous_game_mode: i8,
+    dimension_names: Vec<String>,
+    registry_codec: NamedNbt,
+    dimension_type: String,
+    dimension_name: String,
+    hashed_seed: i64,
+    #[varint]
+    max_players: i32,
+    #[varint]
+    view_distance: i32,
+    #[varint]
+    simulation_distance: i32,
+    reduced_debug_info: bool,
+    enable_respawn_screen: bool,
+    is_debug: bool,
+    is_flat: bool,
+    death_location: Option<DeathLocation>,
+}
+
+impl From<Login1_19> for Login {
+    fn from(old: Login1_19) -> Self {
+        Self {
+            entity_id: old.entity_id,
+            is_hardcore: old.is_hardcore,
+            dimension_names: old.dimension_names,
+            max_players: old.max_players,
+            view_distance: old.view_distance,
+            simulation_distance: old.simulation_distance,
+            reduced_debug_info: old.reduced_debug_info,
+            enable_respawn_screen: old.enable_respawn_screen,
+            do_limited_crafting: false,
+            dimension_type: old.dimension_type,
+            dimension_name: old.dimension_name,
+            hashed_seed: old.hashed_seed,
+            game_mode: old.game_mode,
+            previous_game_mode: old.previous_game_mode,
+            is_debug: old.is_debug,
+            is_flat: old.is_flat,
+            death_location: old.death_location,
+            portal_cooldown: 0,
+            registry_codec: NetworkNbt(Some(old.registry_codec.value)),
+        }
+    }
+}
+
+impl From<&Login> for Login1_19 {
+    fn from(login: &Login) -> Self {
+        Self {
+            entity_id: login.entity_id,
+            is_hardcore: login.is_hardcore,
+            game_mode: login.game_mode,
+            previous_game_mode: login.previous_game_mode,
+            dimension_names: login.dimension_names.clone(),
+            registry_codec: named(&login.registry_codec),
+            dimension_type: login.dimension_type.clone(),
+            dimension_name: login.dimension_name.clone(),
+            hashed_seed: login.hashed_seed,
+            max_players: login.max_players,
+            view_distance: login.view_distance,
+            simulation_distance: login.simulation_distance,
+            reduced_debug_info: login.reduced_debug_info,
+            enable_respawn_screen: login.enable_respawn_screen,
+            is_debug: login.is_debug,
+            is_flat: login.is_flat,
+            death_location: login.death_location.clone(),
+        }
+    }
+}
+
+/// Before 1.20.2, which moved the registry codec to the configuration state and
+/// reordered the fields
+#[derive(FromBytes, ToBytes)]
+struct Login1_20 {
+    entity_id: i32,
+    is_hardcore: bool,
+    game_mode: u8,
+    previous_game_mode: i8,
+    dimension_names: Vec<String>,
+    registry_codec: NamedNbt,
+    dimension_type: String,
+    dimension_name: String,
+    hashed_seed: i64,
+    #[varint]
+    max_players: i32,
+    #[varint]
+    view_distance: i32,
+    #[varint]
+    simulation_distance: i32,
+    reduced_debug_info: bool,
+    enable_respawn_screen: bool,
+    is_debug: bool,
+    is_flat: bool,
+    death_location: Option<DeathLocation>,
+    #[varint]
+    portal_cooldown: i32,
+}
+
+impl From<Login1_20> for Login {
+    fn from(old: Login1_20) -> Self {
+        Self {
+            entity_id: old.entity_id,
+            is_hardcore: old.is_hardcore,
+            dimension_names: old.dimension_names,
+            max_players: old.max_players,
+            view_distance: old.view_distance,
+            simulation_distance: old.simulation_distance,
+            reduced_debug_info: old.reduced_debug_info,
+            enable_respawn_screen: old.enable_respawn_screen,
+            do_limited_crafting: false,
+            dimension_type: old.dimension_type,
+            dimension_name: old.dimension_name,
+            hashed_seed: old.hashed_seed,
+            game_mode: old.game_mode,
+            previous_game_mode: old.previous_game_mode,
+            is_debug: old.is_debug,
+            is_flat: old.is_flat,
+            death_location: old.death_location,
+            portal_cooldown: old.portal_cooldown,
+            registry_codec: NetworkNbt(Some(old.registry_codec.value)),
+        }
+    }
+}
+
+impl From<&Login> for Login1_20 {
+    fn from(login: &Login) -> Self {
+        Self {
+            entity_id: login.entity_id,
+            is_hardcore: login.is_hardcore,
+            game_mode: login.game_mode,
+            previous_game_mode: login.previous_game_mode,
+            dimension_names: login.dimension_names.clone(),
+            registry_codec: named(&login.registry_codec),
+            dimension_type: login.dimension_type.clone(),
+            dimension_name: login.dimension_name.clone(),
+            hashed_seed: login.hashed_seed,
+            max_players: login.max_players,
+            view_distance: login.view_distance,
+            simulation_distance: login.simulation_distance,
+            reduced_debug_info: login.reduced_debug_info,
+            enable_respawn_screen: login.enable_respawn_screen,
+            is_debug: login.is_debug,
+            is_flat: login.is_flat,
+            death_location: login.death_location.clone(),
+            portal_cooldown: login.portal_cooldown,
+        }
+    }
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct DeathLocation {
+    pub dimension_name: String,
+    pub location: Position,
+}
+
+/// Moves an entity by less than 8 blocks
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct UpdateEntityPosition {
+    #[varint]
+    pub entity_id: i32,
+    /// In 1/4096 blocks
+    pub delta_x: i16,
+    pub delta_y: i16,
+    pub delta_z: i16,
+    pub on_ground: bool,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct UpdateEntityPositionAndRotation {
+    #[varint]
+    pub entity_id: i32,
+    pub delta_x: i16,
+    pub delta_y: i16,
+    pub delta_z: i16,
+    #[angle]
+    pub yaw: f32,
+    #[angle]
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct UpdateEntityRotation {
+    #[varint]
+    pub entity_id: i32,
+    #[angle]
+    pub yaw: f32,
+    #[angle]
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct SynchronizePlayerPosition {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Bit field of the coordinates that are relative, x, y, z, yaw and pitch from the
+    /// least significant bit
+    pub flags: u8,
+    /// Echoed by the client in [`ConfirmTeleportation`]
+    #[varint]
+    pub teleport_id: i32,
+}
+
+/// Before 1.19.4, which removed `dismount_vehicle`
+#[derive(FromBytes, ToBytes)]
+struct SynchronizePlayerPosition1_19_3 {
+    x: f64,
+    y: f64,
+    z: f64,
+    yaw: f32,
+    pitch: f32,
+    flags: u8,
+    #[varint]
+    teleport_id: i32,
+    dismount_vehicle: bool,
+}
+
+impl From<SynchronizePlayerPosition1_19_3> for SynchronizePlayerPosition {
+    fn from(old: SynchronizePlayerPosition1_19_3) -> Self {
+        Self {
+            x: old.x,
+            y: old.y,
+            z: old.z,
+            yaw: old.yaw,
+            pitch: old.pitch,
+            flags: old.flags,
+            teleport_id: old.teleport_id,
+        }
+    }
+}
+
+impl From<&SynchronizePlayerPosition> for SynchronizePlayerPosition1_19_3 {
+    fn from(sync: &SynchronizePlayerPosition) -> Self {
+        Self {
+            x: sync.x,
+            y: sync.y,
+            z: sync.z,
+            yaw: sync.yaw,
+            pitch: sync.pitch,
+            flags: sync.flags,
+            teleport_id: sync.teleport_id,
+            dismount_vehicle: false,
+        }
+    }
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct RemoveEntities {
+    pub entity_ids: Vec<VarInt>,
+}
+
+/// The chunk the player is in, for loading chunks around it
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct SetCenterChunk {
+    #[varint]
+    pub chunk_x: i32,
+    #[varint]
+    pub chunk_z: i32,
+}
+
+/// Where the compass points, and where players spawn
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct SetDefaultSpawnPosition {
+    pub location: Position,
+    pub angle: f32,
+}
+
+#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+pub struct SystemChatMessage {
+    pub content: TextComponent,
+    /// Shown above the hotbar instead of in the chat
+    pub overlay: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{nbt::Nbt, version::ForVersion};
+
+    fn round_trip(packet: &CBPlay, version: ProtocolVersion) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let written = ForVersion(packet, version).write_to(&mut bytes).unwrap();
+        assert_eq!(ForVersion(packet, version).encoded_size(), written);
+
+        assert_eq!(
+            &CBPlay::read_versioned(&mut &bytes[..], version).unwrap(),
+            packet
+        );
+
+        bytes
+    }
+
+    #[test]
+    fn play_packets_per_version() {
+        let mut login = Login {
+            entity_id: 1,
+            is_hardcore: false,
+            dimension_names: vec!["minecraft:overworld".to_string()],
+            max_players: 20,
+            view_distance: 10,
+            simulation_distance: 10,
+            reduced_debug_info: false,
+            enable_respawn_screen: true,
+            do_limited_crafting: false,
+            dimension_type: "minecraft:overworld".to_string(),
+            dimension_name: "minecraft:overworld".to_string(),
+            hashed_seed: 0,
+            game_mode: 1,
+            previous_game_mode: -1,
+            is_debug: false,
+            is_flat: true,
+            death_location: None,
+            portal_cooldown: 0,
+            registry_codec: NetworkNbt::default(),
+        };
+        // Sent in the configuration state instead
+        let latest = round_trip(&CBPlay::Login(login.clone()), ProtocolVersion::V1_20_2);
+
+        login.registry_codec = NetworkNbt(Some(Nbt::Compound(Default::default())));
+        let login = CBPlay::Login(login);
+        let old = round_trip(&login, ProtocolVersion::V1_19_4);
+        let new = round_trip(&login, ProtocolVersion::V1_20);
+        assert_eq!((old[0], new[0], latest[0]), (0x28, 0x28, 0x29));
+        // Without the portal cooldown
+        assert_eq!(old.len() + 1, new.len());
+        // With `do_limited_crafting`, but without the 4 byte named empty compound
+        assert_eq!(latest.len() + 3, new.len());
+        assert_eq!(round_trip(&login, ProtocolVersion::V1_19_3)[0], 0x24);
+
+        let unload = CBPlay::UnloadChunk(UnloadChunk {
+            chunk_z: 1,
+            chunk_x: 2,
+        });
+        assert_eq!(
+            round_trip(&unload, ProtocolVersion::V1_20),
+            b"\x1E\0\0\0\x02\0\0\0\x01"
+        );
+        assert_eq!(
+            round_trip(&unload, ProtocolVersion::V1_20_2),
+            b"\x1F\0\0\0\x01\0\0\0\x02"
+        );
+
+        let chunk = CBPlay::ChunkData(ChunkData {
+            chunk_x: 0,
+            chunk_z: 0,
+            heightmaps: NetworkNbt(Some(Nbt::Compound(Default::default()))),
+            data: Vec::new(),
+            block_entities: vec![ChunkBlockEntity {
+                packed_xz: 0,
+                y: 64,
+                block_entity_type: 7,
+                data: NetworkNbt(Some(Nbt::Compound(Default::default()))),
+            }],
+            light: LightData::default(),
+        });
+        let named = round_trip(&chunk, ProtocolVersion::V1_20);
+        // The two root tags lose their empty names
+        assert_eq!(
+            round_trip(&chunk, ProtocolVersion::V1_20_2).len() + 4,
+            named.len()
+        );
+        round_trip(&chunk, ProtocolVersion::V1_19_3);
+
+        let keep_alive = SBPlay::KeepAlive(KeepAlive { id: 7 });
+        let mut bytes = Vec::new();
+        ForVersion(&keep_alive, ProtocolVersion::V1_19_3)
+            .write_to(&mut bytes)
+            .unwrap();
+        assert_eq!(bytes, b"\x11\0\0\0\0\0\0\0\x07");
+
+        let read =
+            SBPlay::read_versioned(&mut &b"\x12\0\0\0\0\0\0\0\x07"[..], ProtocolVersion::V1_20);
+        assert_eq!(read.unwrap(), keep_alive);
+        let read = SBPlay::read_versioned(
+            &mut &b"\x14\0\0\0\0\0\0\0\x07"[..],
+            ProtocolVersion::V1_20_2,
+        );
+        assert_eq!(read.unwrap(), keep_alive);
+    }
+}
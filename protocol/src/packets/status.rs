@@ -1,16 +1,16 @@
-use crate::{FromBytes, ToBytes};
+use crate::{FromBytes, FromBytesVersioned, TextComponent, ToBytes, ToBytesVersioned};
 use base64::Engine;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value as JsonValue};
 use uuid::Uuid;
 
-#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+#[derive(FromBytes, ToBytes, FromBytesVersioned, ToBytesVersioned, Debug, Clone, PartialEq)]
 pub enum SBStatus {
     StatusRequest,
     PingRequest(PingRequest),
 }
 
-#[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+#[derive(FromBytes, ToBytes, FromBytesVersioned, ToBytesVersioned, Debug, Clone, PartialEq)]
 pub enum CBStatus {
     StatusResponse(StatusResponse),
     PingResponse(PingResponse),
@@ -61,14 +61,8 @@ impl StatusResponseBuilder {
 
         self
     }
-    // todo chat object newtype
-    pub fn description_raw(mut self, description: JsonValue) -> Self {
-        self.json["description"] = description;
-
-        self
-    }
-    pub fn description(mut self, description: String) -> Self {
-        self.json["description"] = json!(description);
+    pub fn description(mut self, description: impl Into<TextComponent>) -> Self {
+        self.json["description"] = json!(description.into());
 
         self
     }
@@ -0,0 +1,607 @@
+//! Text components, the JSON chat format used by status responses, disconnects and chat
+
+use crate::{FromBytes, ToBytes, VarInt};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    borrow::Cow,
+    io::{self, Read, Write},
+};
+use uuid::Uuid;
+
+/// A piece of formatted text, with children that inherit its style
+///
+/// Built with the constructors and chained setters:
+///
+/// ```
+/// # use protocol::text::{Color, TextComponent};
+/// let text = TextComponent::text("Hello ")
+///     .color(Color::Gold)
+///     .append(TextComponent::text("world").bold(true));
+///
+/// assert_eq!(text.to_legacy(), "§6Hello §6§lworld");
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(from = "Repr")]
+pub struct TextComponent {
+    #[serde(flatten)]
+    pub content: Content,
+    #[serde(flatten)]
+    pub style: Style,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra: Vec<TextComponent>,
+}
+
+/// What a component displays
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Content {
+    Text {
+        text: String,
+    },
+    /// A translation key, with arguments for its placeholders
+    Translate {
+        translate: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        with: Vec<TextComponent>,
+    },
+    Score {
+        score: Score,
+    },
+    /// Entity selector, resolved to entity names by the server
+    Selector {
+        selector: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        separator: Option<Box<TextComponent>>,
+    },
+    /// The key bound to a control, such as `key.jump`
+    Keybind {
+        keybind: String,
+    },
+}
+
+impl Default for Content {
+    fn default() -> Self {
+        Self::Text {
+            text: String::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Score {
+    /// Player name or entity selector
+    pub name: String,
+    pub objective: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+/// Formatting, unset fields are inherited from the parent
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Style {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<Color>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bold: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub italic: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub underlined: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strikethrough: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub obfuscated: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font: Option<String>,
+    /// Inserted into the chat input when shift-clicked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub insertion: Option<String>,
+    #[serde(
+        rename = "clickEvent",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub click_event: Option<Box<ClickEvent>>,
+    #[serde(
+        rename = "hoverEvent",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub hover_event: Option<Box<HoverEvent>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Black,
+    DarkBlue,
+    DarkGreen,
+    DarkAqua,
+    DarkRed,
+    DarkPurple,
+    Gold,
+    Gray,
+    DarkGray,
+    Blue,
+    Green,
+    Aqua,
+    Red,
+    LightPurple,
+    Yellow,
+    White,
+    /// `0xRRGGBB`, which has no legacy code
+    Rgb(u32),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "action", content = "value", rename_all = "snake_case")]
+pub enum ClickEvent {
+    OpenUrl(String),
+    RunCommand(String),
+    SuggestCommand(String),
+    /// Page number in a book, as a string
+    ChangePage(String),
+    CopyToClipboard(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "action", content = "contents", rename_all = "snake_case")]
+pub enum HoverEvent {
+    ShowText(Box<TextComponent>),
+    ShowItem {
+        id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        count: Option<i32>,
+        /// SNBT of the item's NBT
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tag: Option<String>,
+    },
+    ShowEntity {
+        #[serde(rename = "type")]
+        kind: String,
+        id: Uuid,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<Box<TextComponent>>,
+    },
+}
+
+impl TextComponent {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::from_content(Content::Text { text: text.into() })
+    }
+    pub fn translate(key: impl Into<String>, with: Vec<TextComponent>) -> Self {
+        Self::from_content(Content::Translate {
+            translate: key.into(),
+            with,
+        })
+    }
+    pub fn score(name: impl Into<String>, objective: impl Into<String>) -> Self {
+        Self::from_content(Content::Score {
+            score: Score {
+                name: name.into(),
+                objective: objective.into(),
+                value: None,
+            },
+        })
+    }
+    pub fn selector(selector: impl Into<String>) -> Self {
+        Self::from_content(Content::Selector {
+            selector: selector.into(),
+            separator: None,
+        })
+    }
+    pub fn keybind(keybind: impl Into<String>) -> Self {
+        Self::from_content(Content::Keybind {
+            keybind: keybind.into(),
+        })
+    }
+    fn from_content(content: Content) -> Self {
+        Self {
+            content,
+            ..Default::default()
+        }
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.style.color = Some(color);
+        self
+    }
+    pub fn bold(mut self, bold: bool) -> Self {
+        self.style.bold = Some(bold);
+        self
+    }
+    pub fn italic(mut self, italic: bool) -> Self {
+        self.style.italic = Some(italic);
+        self
+    }
+    pub fn underlined(mut self, underlined: bool) -> Self {
+        self.style.underlined = Some(underlined);
+        self
+    }
+    pub fn strikethrough(mut self, strikethrough: bool) -> Self {
+        self.style.strikethrough = Some(strikethrough);
+        self
+    }
+    pub fn obfuscated(mut self, obfuscated: bool) -> Self {
+        self.style.obfuscated = Some(obfuscated);
+        self
+    }
+    pub fn font(mut self, font: impl Into<String>) -> Self {
+        self.style.font = Some(font.into());
+        self
+    }
+    pub fn insertion(mut self, insertion: impl Into<String>) -> Self {
+        self.style.insertion = Some(insertion.into());
+        self
+    }
+    pub fn click_event(mut self, event: ClickEvent) -> Self {
+        self.style.click_event = Some(Box::new(event));
+        self
+    }
+    pub fn hover_event(mut self, event: HoverEvent) -> Self {
+        self.style.hover_event = Some(Box::new(event));
+        self
+    }
+    /// Adds a child, which inherits this component's style
+    pub fn append(mut self, child: impl Into<TextComponent>) -> Self {
+        self.extra.push(child.into());
+        self
+    }
+
+    /// Parses text with legacy `§` formatting codes
+    ///
+    /// Like in vanilla, colour codes reset the formatting codes before them.
+    pub fn from_legacy(legacy: &str) -> Self {
+        let mut root = Self::text("");
+        let mut style = Style::default();
+        let mut text = String::new();
+        let mut chars = legacy.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '§' {
+                text.push(c);
+                continue;
+            }
+            let Some(code) = chars.next() else {
+                break;
+            };
+
+            if !text.is_empty() {
+                root.extra.push(Self {
+                    content: Content::Text {
+                        text: std::mem::take(&mut text),
+                    },
+                    style: style.clone(),
+                    extra: Vec::new(),
+                });
+            }
+
+            match code.to_ascii_lowercase() {
+                'k' => style.obfuscated = Some(true),
+                'l' => style.bold = Some(true),
+                'm' => style.strikethrough = Some(true),
+                'n' => style.underlined = Some(true),
+                'o' => style.italic = Some(true),
+                'r' => style = Style::default(),
+                code => {
+                    if let Some(color) = Color::from_legacy_code(code) {
+                        style = Style {
+                            color: Some(color),
+                            ..Default::default()
+                        };
+                    }
+                }
+            }
+        }
+
+        if !text.is_empty() {
+            root.extra.push(Self {
+                content: Content::Text { text },
+                style,
+                extra: Vec::new(),
+            });
+        }
+
+        // Without any formatting, a plain text component is enough
+        match root.extra.as_slice() {
+            [only] if only.style == Style::default() => only.clone(),
+            _ => root,
+        }
+    }
+    /// The text with legacy `§` formatting codes, for clients and tools that don't
+    /// support components
+    ///
+    /// Only text, colours and formatting are kept. RGB colours are dropped, and
+    /// translations, keybinds and selectors are shown unresolved.
+    pub fn to_legacy(&self) -> String {
+        let mut legacy = String::new();
+        self.write_legacy(&Style::default(), &mut Style::default(), &mut legacy);
+
+        legacy
+    }
+    fn write_legacy(&self, parent: &Style, current: &mut Style, legacy: &mut String) {
+        let style = self.style.inherit(parent);
+        let text = self.content.plain();
+
+        if !text.is_empty() {
+            if style.legacy_codes() != current.legacy_codes() {
+                legacy.push_str(&style.legacy_codes());
+                *current = style.clone();
+            }
+            legacy.push_str(&text);
+        }
+
+        for child in &self.extra {
+            child.write_legacy(&style, current, legacy);
+        }
+    }
+}
+
+impl Content {
+    /// The displayed text, as far as it's known without a client
+    fn plain(&self) -> Cow<'_, str> {
+        match self {
+            Content::Text { text } => text.into(),
+            Content::Translate { translate, .. } => translate.into(),
+            Content::Score { score } => score.value.as_deref().unwrap_or_default().into(),
+            Content::Selector { selector, .. } => selector.into(),
+            Content::Keybind { keybind } => keybind.into(),
+        }
+    }
+}
+
+impl Style {
+    /// This style with unset fields taken from the parent
+    pub fn inherit(&self, parent: &Style) -> Style {
+        Style {
+            color: self.color.or(parent.color),
+            bold: self.bold.or(parent.bold),
+            italic: self.italic.or(parent.italic),
+            underlined: self.underlined.or(parent.underlined),
+            strikethrough: self.strikethrough.or(parent.strikethrough),
+            obfuscated: self.obfuscated.or(parent.obfuscated),
+            font: self.font.clone().or_else(|| parent.font.clone()),
+            insertion: self.insertion.clone().or_else(|| parent.insertion.clone()),
+            click_event: self
+                .click_event
+                .clone()
+                .or_else(|| parent.click_event.clone()),
+            hover_event: self
+                .hover_event
+                .clone()
+                .or_else(|| parent.hover_event.clone()),
+        }
+    }
+    /// Codes that switch to this style from any other
+    fn legacy_codes(&self) -> String {
+        let mut codes = match self.color.and_then(Color::legacy_code) {
+            Some(code) => format!("§{code}"),
+            None => "§r".to_string(),
+        };
+
+        let formats = [
+            (self.obfuscated, 'k'),
+            (self.bold, 'l'),
+            (self.strikethrough, 'm'),
+            (self.underlined, 'n'),
+            (self.italic, 'o'),
+        ];
+        for (_, code) in formats.iter().filter(|(set, _)| *set == Some(true)) {
+            codes.push('§');
+            codes.push(*code);
+        }
+
+        codes
+    }
+}
+
+const COLORS: [(Color, &str, char); 16] = [
+    (Color::Black, "black", '0'),
+    (Color::DarkBlue, "dark_blue", '1'),
+    (Color::DarkGreen, "dark_green", '2'),
+    (Color::DarkAqua, "dark_aqua", '3'),
+    (Color::DarkRed, "dark_red", '4'),
+    (Color::DarkPurple, "dark_purple", '5'),
+    (Color::Gold, "gold", '6'),
+    (Color::Gray, "gray", '7'),
+    (Color::DarkGray, "dark_gray", '8'),
+    (Color::Blue, "blue", '9'),
+    (Color::Green, "green", 'a'),
+    (Color::Aqua, "aqua", 'b'),
+    (Color::Red, "red", 'c'),
+    (Color::LightPurple, "light_purple", 'd'),
+    (Color::Yellow, "yellow", 'e'),
+    (Color::White, "white", 'f'),
+];
+
+impl Color {
+    pub fn legacy_code(self) -> Option<char> {
+        COLORS
+            .iter()
+            .find(|(c, ..)| *c == self)
+            .map(|(.., code)| *code)
+    }
+    pub fn from_legacy_code(code: char) -> Option<Self> {
+        COLORS
+            .iter()
+            .find(|(.., c)| *c == code)
+            .map(|(color, ..)| *color)
+    }
+    /// The name used in JSON, `#RRGGBB` for RGB colours
+    pub fn name(self) -> Cow<'static, str> {
+        match self {
+            Color::Rgb(rgb) => format!("#{rgb:06X}").into(),
+            _ => COLORS
+                .iter()
+                .find(|(c, ..)| *c == self)
+                .map(|(_, name, _)| *name)
+                .unwrap_or_default()
+                .into(),
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some(hex) = name.strip_prefix('#') {
+            return u32::from_str_radix(hex, 16)
+                .ok()
+                .filter(|rgb| hex.len() == 6 && *rgb <= 0xFFFFFF)
+                .map(Color::Rgb);
+        }
+
+        COLORS.iter().find(|(_, n, _)| *n == name).map(|(c, ..)| *c)
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = Cow::<str>::deserialize(deserializer)?;
+
+        Color::from_name(&name)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown color {name}")))
+    }
+}
+
+/// Components can also be plain strings, or arrays where the rest are children of the first
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Repr {
+    Text(String),
+    List(Vec<TextComponent>),
+    Object {
+        #[serde(flatten)]
+        content: Content,
+        #[serde(flatten)]
+        style: Style,
+        #[serde(default)]
+        extra: Vec<TextComponent>,
+    },
+}
+
+impl From<Repr> for TextComponent {
+    fn from(repr: Repr) -> Self {
+        match repr {
+            Repr::Text(text) => Self::text(text),
+            Repr::List(list) => {
+                let mut list = list.into_iter();
+                let first = list.next().unwrap_or_default();
+
+                list.fold(first, TextComponent::append)
+            }
+            Repr::Object {
+                content,
+                style,
+                extra,
+            } => Self {
+                content,
+                style,
+                extra,
+            },
+        }
+    }
+}
+
+impl From<&str> for TextComponent {
+    fn from(text: &str) -> Self {
+        Self::text(text)
+    }
+}
+
+impl From<String> for TextComponent {
+    fn from(text: String) -> Self {
+        Self::text(text)
+    }
+}
+
+impl ToBytes for TextComponent {
+    fn write_to<W: Write>(&self, write: &mut W) -> io::Result<usize> {
+        serde_json::to_string(self)?.write_to(write)
+    }
+    fn encoded_size(&self) -> usize {
+        // Only maps with non-string keys fail to serialize, which components don't have
+        let length = serde_json::to_string(self).map_or(0, |s| s.len());
+
+        VarInt(length as i32).encoded_size() + length
+    }
+}
+
+impl FromBytes for TextComponent {
+    fn read_from<R: Read>(read: &mut R) -> io::Result<Self> {
+        let json = String::read_from(read)?;
+
+        serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClickEvent, Color, HoverEvent, TextComponent};
+    use crate::{FromBytes, ToBytes};
+    use serde_json::json;
+
+    #[test]
+    fn json_and_legacy() {
+        let text = TextComponent::text("Click ")
+            .color(Color::Green)
+            .append(
+                TextComponent::text("here")
+                    .underlined(true)
+                    .click_event(ClickEvent::OpenUrl("https://example.com".to_string()))
+                    .hover_event(HoverEvent::ShowText(Box::new("Opens a link".into()))),
+            )
+            .append(TextComponent::translate(
+                "chat.type.text",
+                vec![TextComponent::selector("@p")],
+            ))
+            .append(TextComponent::text("!").color(Color::Rgb(0x12AB34)));
+
+        let value = serde_json::to_value(&text).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "text": "Click ",
+                "color": "green",
+                "extra": [
+                    {
+                        "text": "here",
+                        "underlined": true,
+                        "clickEvent": { "action": "open_url", "value": "https://example.com" },
+                        "hoverEvent": {
+                            "action": "show_text",
+                            "contents": { "text": "Opens a link" },
+                        },
+                    },
+                    { "translate": "chat.type.text", "with": [{ "selector": "@p" }] },
+                    { "text": "!", "color": "#12AB34" },
+                ],
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<TextComponent>(value).unwrap(),
+            text
+        );
+
+        let mut bytes = Vec::new();
+        text.write_to(&mut bytes).unwrap();
+        assert_eq!(text.encoded_size(), bytes.len());
+        assert_eq!(TextComponent::read_from(&mut &bytes[..]).unwrap(), text);
+
+        // Shorthands
+        let short: TextComponent =
+            serde_json::from_value(json!(["a", { "keybind": "key.jump" }])).unwrap();
+        assert_eq!(
+            short,
+            TextComponent::text("a").append(TextComponent::keybind("key.jump"))
+        );
+
+        assert_eq!(text.to_legacy(), "§aClick §a§nhere§achat.type.text§r!");
+        assert_eq!(
+            TextComponent::from_legacy("§aClick §lhere§r!").to_legacy(),
+            "§aClick §a§lhere§r!"
+        );
+        assert_eq!(TextComponent::from_legacy("plain"), "plain".into());
+    }
+}
@@ -1,11 +1,26 @@
-use crate::{newtypes::VarLong, ToBytes, VarInt};
+use crate::{
+    newtypes::{TrailingBytes, VarLong},
+    ToBytes, VarInt,
+};
 use serde_json::Value;
 use std::io::{Result, Write};
 use uuid::Uuid;
 
+impl<T: ToBytes + ?Sized> ToBytes for &T {
+    fn write_to<W: Write>(&self, write: &mut W) -> Result<usize> {
+        (**self).write_to(write)
+    }
+    fn encoded_size(&self) -> usize {
+        (**self).encoded_size()
+    }
+}
+
 impl<T: ToBytes> ToBytes for Box<T> {
     fn write_to<W: Write>(&self, write: &mut W) -> Result<usize> {
-        Ok((**self).write_to(write)?)
+        (**self).write_to(write)
+    }
+    fn encoded_size(&self) -> usize {
+        (**self).encoded_size()
     }
 }
 
@@ -19,12 +34,22 @@ impl<T: ToBytes> ToBytes for Box<[T]> {
 
         Ok(written)
     }
+    fn encoded_size(&self) -> usize {
+        self.iter().map(ToBytes::encoded_size).sum()
+    }
 }
 
 impl ToBytes for Value {
     fn write_to<W: Write>(&self, write: &mut W) -> Result<usize> {
         serde_json::to_string(self)?.write_to(write)
     }
+    fn encoded_size(&self) -> usize {
+        // Serializing to a String can only fail with non-string map keys,
+        // which a Value can't have
+        let length = serde_json::to_string(self).map_or(0, |s| s.len());
+
+        VarInt(length as i32).encoded_size() + length
+    }
 }
 
 impl<T: ToBytes> ToBytes for Option<T> {
@@ -43,9 +68,12 @@ impl<T: ToBytes> ToBytes for Option<T> {
 
         Ok(written)
     }
+    fn encoded_size(&self) -> usize {
+        1 + self.as_ref().map_or(0, ToBytes::encoded_size)
+    }
 }
 
-impl ToBytes for String {
+impl ToBytes for str {
     fn write_to<W: Write>(&self, write: &mut W) -> std::io::Result<usize> {
         let mut written = 0;
 
@@ -64,11 +92,26 @@ impl ToBytes for String {
 
         Ok(written)
     }
+    fn encoded_size(&self) -> usize {
+        VarInt(self.len() as i32).encoded_size() + self.len()
+    }
+}
+
+impl ToBytes for String {
+    fn write_to<W: Write>(&self, write: &mut W) -> Result<usize> {
+        self.as_str().write_to(write)
+    }
+    fn encoded_size(&self) -> usize {
+        self.as_str().encoded_size()
+    }
 }
 
 impl ToBytes for Uuid {
     fn write_to<W: Write>(&self, write: &mut W) -> std::io::Result<usize> {
-        Ok(self.as_u128().write_to(write)?)
+        self.as_u128().write_to(write)
+    }
+    fn encoded_size(&self) -> usize {
+        16
     }
 }
 
@@ -99,6 +142,12 @@ impl ToBytes for VarInt {
 
         Ok(i)
     }
+    fn encoded_size(&self) -> usize {
+        // 7 bits per byte, at least one byte
+        let bits = 32 - (self.0 as u32).leading_zeros() as usize;
+
+        bits.max(1).div_ceil(7)
+    }
 }
 
 impl ToBytes for VarLong {
@@ -129,9 +178,15 @@ impl ToBytes for VarLong {
 
         Ok(i)
     }
+    fn encoded_size(&self) -> usize {
+        // 7 bits per byte, at least one byte
+        let bits = 64 - (self.0 as u64).leading_zeros() as usize;
+
+        bits.max(1).div_ceil(7)
+    }
 }
 
-impl<T: ToBytes> ToBytes for Vec<T> {
+impl<T: ToBytes> ToBytes for [T] {
     fn write_to<W: Write>(&self, write: &mut W) -> std::io::Result<usize> {
         let mut written = 0;
 
@@ -151,6 +206,46 @@ impl<T: ToBytes> ToBytes for Vec<T> {
 
         Ok(written)
     }
+    fn encoded_size(&self) -> usize {
+        VarInt(self.len() as i32).encoded_size()
+            + self.iter().map(ToBytes::encoded_size).sum::<usize>()
+    }
+}
+
+impl<T: ToBytes> ToBytes for Vec<T> {
+    fn write_to<W: Write>(&self, write: &mut W) -> Result<usize> {
+        self.as_slice().write_to(write)
+    }
+    fn encoded_size(&self) -> usize {
+        self.as_slice().encoded_size()
+    }
+}
+
+/// Fixed-size, so not length-prefixed, unlike slices
+impl<T: ToBytes, const N: usize> ToBytes for [T; N] {
+    fn write_to<W: Write>(&self, write: &mut W) -> Result<usize> {
+        let mut written = 0;
+
+        for e in self {
+            written += e.write_to(write)?;
+        }
+
+        Ok(written)
+    }
+    fn encoded_size(&self) -> usize {
+        self.iter().map(ToBytes::encoded_size).sum()
+    }
+}
+
+impl ToBytes for TrailingBytes<'_> {
+    fn write_to<W: Write>(&self, write: &mut W) -> Result<usize> {
+        write.write_all(self.0)?;
+
+        Ok(self.0.len())
+    }
+    fn encoded_size(&self) -> usize {
+        self.0.len()
+    }
 }
 
 impl ToBytes for bool {
@@ -159,6 +254,9 @@ impl ToBytes for bool {
 
         Ok(1)
     }
+    fn encoded_size(&self) -> usize {
+        1
+    }
 }
 
 macro_rules! implement_for_primitive {
@@ -171,6 +269,9 @@ macro_rules! implement_for_primitive {
 
                     Ok(buf.len())
                 }
+                fn encoded_size(&self) -> usize {
+                    ::std::mem::size_of::<$primitive>()
+                }
             }
         )+
 
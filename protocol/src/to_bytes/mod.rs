@@ -4,4 +4,87 @@ use std::io::{Result, Write};
 
 pub trait ToBytes {
     fn write_to<W: Write>(&self, write: &mut W) -> Result<usize>;
+    /// The exact number of bytes `write_to` would write, without writing anything
+    ///
+    /// By default, it writes to a sink that only counts the bytes, so it's worth
+    /// implementing when the size is cheaper to work out. If writing fails, it's the number
+    /// of bytes written before the error.
+    fn encoded_size(&self) -> usize {
+        let mut counter = ByteCounter(0);
+        let _ = self.write_to(&mut counter);
+
+        counter.0
+    }
+}
+
+/// Discards everything written to it, counting the bytes
+struct ByteCounter(usize);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.0 += buf.len();
+
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ToBytes;
+    use crate::{
+        packets::{
+            login::{Disconnect, LoginSuccess, Property},
+            play::KeepAlive,
+            CBLogin, CBPlay, ClientBound,
+        },
+        BString,
+    };
+    use serde_json::json;
+    use std::io::{Result, Write};
+    use uuid::Uuid;
+
+    /// Without its own `encoded_size`
+    struct Greeting;
+
+    impl ToBytes for Greeting {
+        fn write_to<W: Write>(&self, write: &mut W) -> Result<usize> {
+            Ok("Hello".write_to(write)? + 7u32.write_to(write)?)
+        }
+    }
+
+    fn assert_size<T: ToBytes>(value: T) {
+        let mut bytes = Vec::new();
+        let written = value.write_to(&mut bytes).unwrap();
+
+        assert_eq!(value.encoded_size(), written);
+        assert_eq!(written, bytes.len());
+    }
+
+    #[test]
+    fn encoded_size_matches_written() {
+        assert_size(u8::MAX);
+        assert_size(-1i64);
+        assert_size("x".repeat(300));
+        assert_size(Some(vec![Uuid::nil(); 3]));
+        assert_size(None::<bool>);
+        assert_size(json!({ "text": "Hello" }));
+        assert_size(ClientBound::Login(CBLogin::LoginSuccess(LoginSuccess {
+            uuid: Uuid::nil(),
+            username: BString::new("Notch".to_string()).unwrap(),
+            properties: vec![Property {
+                name: BString::new("textures".to_string()).unwrap(),
+                value: BString::new("e30=".to_string()).unwrap(),
+                signature: None,
+            }],
+        })));
+        assert_size(ClientBound::Login(CBLogin::Disconnect(Disconnect {
+            reason: "Bye".into(),
+        })));
+        assert_size(ClientBound::Play(CBPlay::KeepAlive(KeepAlive { id: 1 })));
+        assert_size(Greeting);
+        assert_eq!(Greeting.encoded_size(), 10);
+    }
 }
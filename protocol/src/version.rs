@@ -0,0 +1,170 @@
+//! Protocol versions, and reading/writing packets whose ids and layouts differ between them
+//!
+//! Deriving `FromBytesVersioned`/`ToBytesVersioned` on a packet enum maps its variants
+//! using these attributes, with inclusive version ranges:
+//!
+//! - `#[id(0x1A, until = 762)]` and `#[id(0x1B, since = 763)]` give the packet id per
+//!   version. Variants without them use the same id as the `FromBytes` derive would, in
+//!   all versions. Variants not mapped for a version can't be read or written in it,
+//!   which `ToBytesVersioned::is_available` checks.
+//! - `#[layout(OldLayout, until = 762)]` reads and writes the packet as `OldLayout` in
+//!   those versions, which must implement `From<OldLayout>` for the packet type and
+//!   `From<&Packet>` for itself.
+
+use crate::ToBytes;
+use std::io::{Read, Result, Write};
+
+/// A protocol version number, as sent in the handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProtocolVersion(pub i32);
+
+impl ProtocolVersion {
+    pub const V1_19_3: Self = Self(761);
+    pub const V1_19_4: Self = Self(762);
+    /// 1.20 and 1.20.1
+    pub const V1_20: Self = Self(763);
+    /// Adds the configuration state between login and play
+    pub const V1_20_2: Self = Self(764);
+
+    pub const LATEST: Self = Self::V1_20_2;
+    /// Versions that the packets are mapped for, oldest first
+    pub const SUPPORTED: &'static [Self] =
+        &[Self::V1_19_3, Self::V1_19_4, Self::V1_20, Self::V1_20_2];
+
+    pub fn is_supported(self) -> bool {
+        Self::SUPPORTED.contains(&self)
+    }
+    /// The game version(s) using this protocol version
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            Self::V1_19_3 => Some("1.19.3"),
+            Self::V1_19_4 => Some("1.19.4"),
+            Self::V1_20 => Some("1.20.1"),
+            Self::V1_20_2 => Some("1.20.2"),
+            _ => None,
+        }
+    }
+    /// Human readable range of supported game versions
+    pub fn supported_range() -> String {
+        let oldest = Self::SUPPORTED[0].name().unwrap_or_default();
+        let latest = Self::LATEST.name().unwrap_or_default();
+
+        format!("{oldest}-{latest}")
+    }
+}
+
+impl From<crate::VarInt> for ProtocolVersion {
+    fn from(value: crate::VarInt) -> Self {
+        Self(value.0)
+    }
+}
+
+pub trait FromBytesVersioned: Sized {
+    fn read_versioned<R: Read>(read: &mut R, version: ProtocolVersion) -> Result<Self>;
+}
+
+pub trait ToBytesVersioned {
+    fn write_versioned<W: Write>(&self, write: &mut W, version: ProtocolVersion) -> Result<usize>;
+    /// The exact number of bytes `write_versioned` would write
+    fn versioned_size(&self, version: ProtocolVersion) -> usize;
+    /// Whether the packet is mapped for the version, so that `write_versioned` can write it
+    fn is_available(&self, version: ProtocolVersion) -> bool;
+}
+
+/// A packet to be written for a specific protocol version, usable wherever
+/// [`ToBytes`] is expected, such as the codecs
+pub struct ForVersion<'a, P: ?Sized>(pub &'a P, pub ProtocolVersion);
+
+impl<P: ToBytesVersioned + ?Sized> ToBytes for ForVersion<'_, P> {
+    fn write_to<W: Write>(&self, write: &mut W) -> Result<usize> {
+        self.0.write_versioned(write, self.1)
+    }
+    fn encoded_size(&self) -> usize {
+        self.0.versioned_size(self.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ForVersion, ProtocolVersion};
+    use crate::{FromBytes, FromBytesVersioned, ToBytes, ToBytesVersioned};
+
+    #[derive(FromBytes, ToBytes, Debug, Clone, PartialEq)]
+    struct Chat {
+        message: String,
+        overlay: bool,
+    }
+
+    /// Before `overlay` was added
+    #[derive(FromBytes, ToBytes)]
+    struct OldChat {
+        message: String,
+    }
+
+    impl From<OldChat> for Chat {
+        fn from(old: OldChat) -> Self {
+            Self {
+                message: old.message,
+                overlay: false,
+            }
+        }
+    }
+
+    impl From<&Chat> for OldChat {
+        fn from(chat: &Chat) -> Self {
+            Self {
+                message: chat.message.clone(),
+            }
+        }
+    }
+
+    #[derive(FromBytesVersioned, ToBytesVersioned, Debug, Clone, PartialEq)]
+    enum Packets {
+        KeepAlive(i64),
+        #[id(0x05, until = 761)]
+        #[id(0x06, since = 762)]
+        #[layout(OldChat, until = 761)]
+        Chat(Chat),
+        #[id(0x07, since = 763)]
+        Ping,
+    }
+
+    fn round_trip(packet: &Packets, version: ProtocolVersion) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let written = ForVersion(packet, version).write_to(&mut bytes).unwrap();
+
+        assert_eq!(written, bytes.len());
+        assert_eq!(ForVersion(packet, version).encoded_size(), written);
+
+        let read = Packets::read_versioned(&mut &bytes[..], version).unwrap();
+        assert_eq!(&read, packet);
+
+        bytes
+    }
+
+    #[test]
+    fn versioned_ids_and_layouts() {
+        let chat = Packets::Chat(Chat {
+            message: "hi".to_string(),
+            overlay: false,
+        });
+
+        assert_eq!(round_trip(&chat, ProtocolVersion::V1_19_3), b"\x05\x02hi");
+        assert_eq!(round_trip(&chat, ProtocolVersion::V1_20), b"\x06\x02hi\x00");
+        assert_eq!(
+            round_trip(&Packets::KeepAlive(1), ProtocolVersion::V1_19_4)[0],
+            0x00
+        );
+        assert_eq!(round_trip(&Packets::Ping, ProtocolVersion::V1_20), b"\x07");
+
+        // Not available in older versions
+        let mut bytes = Vec::new();
+        assert!(ForVersion(&Packets::Ping, ProtocolVersion::V1_19_4)
+            .write_to(&mut bytes)
+            .is_err());
+        assert!(Packets::read_versioned(&mut &b"\x07"[..], ProtocolVersion::V1_19_4).is_err());
+        assert!(!Packets::Ping.is_available(ProtocolVersion::V1_19_4));
+        assert!(Packets::Ping.is_available(ProtocolVersion::V1_20));
+        assert!(chat.is_available(ProtocolVersion::V1_19_3));
+    }
+}